@@ -1,129 +1,571 @@
-use crate::errors::AoErrors;
+use crate::bundle::Bundle;
+use crate::data_item::DataItem;
+use crate::message::{check_anchor, TARGET_BYTES};
+use crate::errors::{response_json, response_text, AoErrors, HttpError};
+use crate::process::Process;
+use crate::retry::RetryPolicy;
 use crate::scheme::{
-    ResponseCu, ResponseMu, DATA_PROTOCOL, DEFAULT_CU, DEFAULT_MU, SDK, TYPE_MESSAGE, TYPE_PROCESS,
-    VARIANT,
+    Cron, DryRunRequest, ResponseBundler, ResponseCu, ResponseMu, ResultEdge, ResultsPage,
+    ResultsQuery, SignedMessage, SpawnOptions, DATA_PROTOCOL, DEFAULT_BUNDLER, DEFAULT_CU,
+    DEFAULT_MU, SDK, TYPE_ASSIGNMENT, TYPE_MESSAGE, TYPE_PROCESS, VARIANT,
 };
-use crate::wallet::{SignerTypes, Signers};
-use base64::prelude::BASE64_STANDARD;
+use crate::utils::{random_anchor, HttpConfig};
+use crate::signer::{AoSigner, SignerSource, WalletSigner};
+use crate::wallet::SignerTypes;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
 use base64::Engine;
 use bundlr_sdk;
-use bundlr_sdk::currency::arweave::ArweaveBuilder;
-use bundlr_sdk::currency::solana::{Solana, SolanaBuilder};
-use bundlr_sdk::currency::Currency;
 use bundlr_sdk::tags::Tag;
 use bundlr_sdk::{BundlrTx, Signer};
-use reqwest::header::{HeaderMap, HeaderValue};
-use reqwest::{Client, Method, Request, RequestBuilder, Response, Url};
-use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use sha2::{Digest, Sha256};
+use reqwest::header::{HeaderMap, HeaderValue, LOCATION};
+use reqwest::{Client, Method, RequestBuilder, Response, Url};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Polling schedule used while waiting for a message result on the CU
+#[derive(Debug, Clone, Copy)]
+pub struct WaitConfig {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: f64,
+    pub timeout: Duration,
+}
+
+impl Default for WaitConfig {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(5),
+            multiplier: 2.0,
+            timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+impl WaitConfig {
+    fn next_delay(&self, delay: Duration) -> Duration {
+        delay.mul_f64(self.multiplier).min(self.max_delay)
+    }
+}
+
+pub const DEFAULT_SEND_TIMEOUT: Duration = Duration::from_secs(60);
+pub const DEFAULT_MAX_REDIRECTS: usize = 5;
+pub const DEFAULT_IDEMPOTENCY_CAPACITY: usize = 1024;
+
+/// Configures a `Legacy` client. All the requests of the client go through a
+/// single pooled `reqwest::Client`, either supplied with `client` or built
+/// from the HTTP settings (which are ignored when a client is supplied).
+///
+/// CU redirects are handled by `Legacy` itself, so a supplied client should
+/// not follow them (`reqwest::redirect::Policy::none()`), otherwise the CU a
+/// process is routed to can't be remembered.
+pub struct LegacyBuilder {
+    mu_url: String,
+    cu_url: String,
+    bundler_url: String,
+    signer: SignerSource,
+    client: Option<Client>,
+    http: HttpConfig,
+    send_timeout: Duration,
+    retry: RetryPolicy,
+    max_redirects: usize,
+    idempotency_capacity: usize,
+}
+
+impl LegacyBuilder {
+    pub fn new(signer: SignerTypes) -> Self {
+        Self::from_source(SignerSource::Wallet(signer))
+    }
+
+    /// Signs with a custom `AoSigner`, e.g. a remote KMS
+    pub fn with_signer(signer: Arc<dyn AoSigner>) -> Self {
+        Self::from_source(SignerSource::Custom(signer))
+    }
+
+    fn from_source(signer: SignerSource) -> Self {
+        Self {
+            mu_url: DEFAULT_MU.to_string(),
+            cu_url: DEFAULT_CU.to_string(),
+            bundler_url: DEFAULT_BUNDLER.to_string(),
+            signer,
+            client: None,
+            http: HttpConfig {
+                follow_redirects: false,
+                ..Default::default()
+            },
+            send_timeout: DEFAULT_SEND_TIMEOUT,
+            retry: RetryPolicy::none(),
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            idempotency_capacity: DEFAULT_IDEMPOTENCY_CAPACITY,
+        }
+    }
+
+    pub fn mu_url(mut self, mu_url: String) -> Self {
+        self.mu_url = mu_url;
+        self
+    }
+
+    pub fn cu_url(mut self, cu_url: String) -> Self {
+        self.cu_url = cu_url;
+        self
+    }
+
+    /// Bundler bundles are posted to by `send_bundle`
+    pub fn bundler_url(mut self, bundler_url: String) -> Self {
+        self.bundler_url = bundler_url;
+        self
+    }
+
+    pub fn client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Timeout of every request, unset by default
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.http.timeout = Some(timeout);
+        self
+    }
+
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.http.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// Timeout of the MU submissions, 60 seconds by default
+    pub fn send_timeout(mut self, send_timeout: Duration) -> Self {
+        self.send_timeout = send_timeout;
+        self
+    }
+
+    /// Retries of the MU and CU requests, none by default
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Number of CU redirects followed by a single request, 5 by default
+    pub fn max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    /// Number of signed items kept by `send_idempotent`, 1024 by default.
+    /// The least recently used one is dropped beyond that.
+    pub fn idempotency_capacity(mut self, capacity: usize) -> Self {
+        self.idempotency_capacity = capacity;
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: String) -> Self {
+        self.http.user_agent = user_agent;
+        self
+    }
+
+    pub fn default_headers(mut self, headers: HeaderMap) -> Self {
+        self.http.default_headers = headers;
+        self
+    }
+
+    pub fn build(self) -> Result<Legacy, AoErrors> {
+        let client = match self.client {
+            Some(client) => client,
+            None => self
+                .http
+                .build_client()
+                .map_err(|e| AoErrors::ErrorConstructingClient {
+                    source: Some(e.into()),
+                })?,
+        };
+
+        let (signer, wallet): (Arc<dyn AoSigner>, _) = match self.signer {
+            SignerSource::Wallet(wallet) => {
+                let wallet = Arc::new(
+                    WalletSigner::load(&wallet)
+                        .map_err(|e| AoErrors::ErrorConstructingSigner { source: Some(e) })?,
+                );
+                (wallet.clone(), Some(wallet))
+            }
+            SignerSource::Custom(signer) => (signer, None),
+        };
+
+        Ok(Legacy {
+            mu_url: self.mu_url,
+            cu_url: self.cu_url,
+            bundler_url: self.bundler_url,
+            signer,
+            wallet,
+            client,
+            send_timeout: self.send_timeout,
+            retry: self.retry,
+            max_redirects: self.max_redirects,
+            submitted: Mutex::new(SubmittedItems::new(self.idempotency_capacity)),
+            cu_routes: Mutex::new(HashMap::new()),
+        })
+    }
+}
 
 pub struct Legacy {
     mu_url: String,
     cu_url: String,
-    signer_type: SignerTypes,
-    signer: Signers,
+    bundler_url: String,
+    signer: Arc<dyn AoSigner>,
+    // the same signer when built from a wallet, for `raw_signer`
+    wallet: Option<Arc<WalletSigner>>,
+    client: Client,
+    send_timeout: Duration,
+    retry: RetryPolicy,
+    max_redirects: usize,
+    // signed data items sent through `send_idempotent`
+    submitted: Mutex<SubmittedItems>,
+    // CU urls the router redirected each process to
+    cu_routes: Mutex<HashMap<String, String>>,
 }
 
 impl Legacy {
     pub fn new(mu_url: String, cu_url: String, signer: SignerTypes) -> Result<Self, AoErrors> {
-        Ok(Self {
-            mu_url,
-            cu_url,
-            signer_type: signer.clone(),
-            signer: Self::signer(&signer)?,
-        })
+        LegacyBuilder::new(signer)
+            .mu_url(mu_url)
+            .cu_url(cu_url)
+            .build()
     }
 
     pub fn default_init(signer: SignerTypes) -> Result<Self, AoErrors> {
-        Ok(Self {
-            mu_url: DEFAULT_MU.to_string(),
-            cu_url: DEFAULT_CU.to_string(),
-            signer_type: signer.clone(),
-            signer: Self::signer(&signer)?,
-        })
+        LegacyBuilder::new(signer).build()
     }
 
-    fn new_bundle_item(
-        data: Vec<u8>,
+    pub fn builder(signer: SignerTypes) -> LegacyBuilder {
+        LegacyBuilder::new(signer)
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// The bundlr signer of the wallet, unavailable with a custom `AoSigner`
+    #[deprecated(note = "sign with `sign_message`, or pass your own `AoSigner` to `LegacyBuilder::with_signer`")]
+    pub fn raw_signer(&self) -> Result<&dyn Signer, AoErrors> {
+        self.wallet
+            .as_ref()
+            .ok_or_else(|| AoErrors::InvalidSigner {
+                source: Some("a custom signer has no bundlr signer".into()),
+            })?
+            .wallet()
+            .raw_signer()
+    }
+
+    /// The public key of the wallet, see `AoSigner::owner`. The signer is
+    /// loaded when the client is built, so this can't fail.
+    pub fn owner(&self) -> Vec<u8> {
+        self.signer.owner()
+    }
+
+    /// The address messages are sent from, see `owner`
+    pub fn address(&self) -> String {
+        self.signer.address()
+    }
+
+    // BundlrTx signing only takes synchronous bundlr signers, so the item
+    // is signed as a `DataItem` and parsed back from its serialization.
+    // Items without an anchor get a random one, see `utils::random_anchor`.
+    pub(crate) async fn sign_bundle_item(
+        &self,
         target: String,
+        anchor: Option<&str>,
+        data: Vec<u8>,
         tags: Vec<Tag>,
     ) -> Result<BundlrTx, AoErrors> {
-        BundlrTx::new(
-            BASE64_STANDARD
-                .decode(target)
-                .map_err(|e| AoErrors::Base64ReadingError)?,
-            data,
-            tags,
-        )
-        .map_err(|e| AoErrors::BundlrError)
-    }
-
-    fn signer(signer: &SignerTypes) -> Result<Signers, AoErrors> {
-        match signer {
-            SignerTypes::Solana(p) => Ok(Signers::Solana(
-                SolanaBuilder::new()
-                    .wallet(p)
-                    .build()
-                    .map_err(|_| AoErrors::ErrorConstructingSigner)?,
-            )),
-            SignerTypes::Arweave(keypair_path) => Ok(Signers::Arweave(
-                ArweaveBuilder::new()
-                    .keypair_path(PathBuf::from(keypair_path.clone()))
-                    .build()
-                    .map_err(|_| AoErrors::ErrorConstructingSigner)?,
-            )),
-        }
+        let item = self.sign_data_item(target, anchor, data, tags).await?;
+        BundlrTx::from_bytes(item.to_bytes()).map_err(|e| AoErrors::BundlrError {
+            source: Some(e.into()),
+        })
     }
 
-    pub fn raw_signer(&self) -> Result<&dyn Signer, AoErrors> {
-        match &self.signer {
-            Signers::Solana(solana) => solana.get_signer().map_err(|_| AoErrors::InvalidSigner),
-            Signers::Arweave(ar) => ar.get_signer().map_err(|_| AoErrors::InvalidSigner),
+    async fn sign_data_item(
+        &self,
+        target: String,
+        anchor: Option<&str>,
+        data: Vec<u8>,
+        tags: Vec<Tag>,
+    ) -> Result<DataItem, AoErrors> {
+        let target = BASE64_URL_SAFE_NO_PAD
+            .decode(&target)
+            .map_err(|_| AoErrors::Base64ReadingError)?;
+        if !target.is_empty() && target.len() != TARGET_BYTES {
+            return Err(AoErrors::InvalidMessage(format!(
+                "target must be {} bytes",
+                TARGET_BYTES
+            )));
+        }
+        let anchor = anchor.map_or_else(random_anchor, str::to_string);
+        check_anchor(anchor.as_bytes())?;
+
+        let mut item = DataItem::new(
+            self.signer.signature_type(),
+            self.signer.owner(),
+            target,
+            anchor.into_bytes(),
+            tags,
+            data,
+        )?;
+        let signature = self
+            .signer
+            .sign(item.signature_data()?)
+            .await
+            .map_err(|e| AoErrors::InvalidSigner { source: Some(e) })?;
+        if signature.len() != self.signer.signature_length() {
+            return Err(AoErrors::InvalidSigner {
+                source: Some(
+                    format!(
+                        "signature is {} bytes, expected {}",
+                        signature.len(),
+                        self.signer.signature_length()
+                    )
+                    .into(),
+                ),
+            });
         }
+        item.set_signature(signature)?;
+        Ok(item)
     }
 
-    pub async fn send(
-        &self,
-        process_id: String,
-        data: Vec<u8>,
-        mut tags: Vec<Tag>,
-    ) -> Result<ResponseMu, AoErrors> {
+    fn octet_stream_headers() -> HeaderMap {
         let mut req_headers = HeaderMap::new();
         req_headers.insert(
             "Content-Type",
             HeaderValue::from_str("application/octet-stream").unwrap(),
         );
         req_headers.insert("Accept", HeaderValue::from_str("application/json").unwrap());
+        req_headers
+    }
 
-        let mut create_tx = Self::new_bundle_item(data, process_id, tags)?;
-        let _ = create_tx.sign(self.raw_signer()?).await;
-        let payload = create_tx
+    pub async fn send(
+        &self,
+        process_id: String,
+        data: Vec<u8>,
+        tags: Vec<Tag>,
+    ) -> Result<ResponseMu, AoErrors> {
+        let message = self.sign_message(process_id, data, tags).await?;
+        self.submit_signed(message.bytes).await
+    }
+
+    /// Signs a message to `process_id` without any network I/O. The signed
+    /// item can be posted later, possibly from another host, with
+    /// `submit_signed`.
+    pub async fn sign_message(
+        &self,
+        process_id: String,
+        data: Vec<u8>,
+        tags: Vec<Tag>,
+    ) -> Result<SignedMessage, AoErrors> {
+        let item = self.sign_data_item(process_id, None, data, tags).await?;
+        Ok(SignedMessage {
+            id: item.id(),
+            bytes: item.to_bytes(),
+        })
+    }
+
+    /// Posts a data item signed by `sign_message` to the MU. The item is
+    /// parsed first, but its signature is left to the MU to check.
+    pub async fn submit_signed(&self, bytes: Vec<u8>) -> Result<ResponseMu, AoErrors> {
+        DataItem::from_bytes(&bytes)?;
+        self.post_to_mu(bytes).await
+    }
+
+    /// Sends a message with a caller supplied 32 bytes anchor
+    pub async fn send_with_anchor(
+        &self,
+        process_id: String,
+        data: Vec<u8>,
+        tags: Vec<Tag>,
+        anchor: &str,
+    ) -> Result<ResponseMu, AoErrors> {
+        let payload = self
+            .sign_bundle_item(process_id, Some(anchor), data, tags)
+            .await?
             .as_bytes()
             .map_err(|_| AoErrors::InvalidTransaction)?;
+        self.post_to_mu(payload).await
+    }
 
-        let req = Client::new()
-            .post(&self.mu_url)
-            .body(payload)
-            .headers(req_headers)
-            .timeout(Duration::from_secs(60))
-            .send()
-            .await;
+    /// Sends a message identified by `anchor` (see `utils::random_anchor`).
+    /// The signed item is kept, so retrying with the same anchor after a
+    /// failure resubmits the exact same data item, and therefore the same
+    /// message id, instead of a duplicate message.
+    pub async fn send_idempotent(
+        &self,
+        process_id: String,
+        data: Vec<u8>,
+        tags: Vec<Tag>,
+        anchor: &str,
+    ) -> Result<ResponseMu, AoErrors> {
+        let content = Self::content_hash(&process_id, &data, &tags);
+        let payload = self
+            .idempotent_item(anchor, content, || {
+                self.sign_bundle_item(process_id, Some(anchor), data, tags)
+            })
+            .await?;
+        self.post_to_mu(payload).await
+    }
+
+    // Identifies what an idempotent send carries, so that an anchor reused
+    // for another message is rejected instead of resending the first one
+    pub(crate) fn content_hash(process_id: &str, data: &[u8], tags: &[Tag]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        let mut field = |bytes: &[u8]| {
+            hasher.update((bytes.len() as u64).to_le_bytes());
+            hasher.update(bytes);
+        };
+        field(process_id.as_bytes());
+        field(data);
+        for tag in tags {
+            field(tag.name.as_bytes());
+            field(tag.value.as_bytes());
+        }
+        hasher.finalize().into()
+    }
+
+    pub(crate) async fn idempotent_item<F, Fut>(
+        &self,
+        anchor: &str,
+        content: [u8; 32],
+        sign: F,
+    ) -> Result<Vec<u8>, AoErrors>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<BundlrTx, AoErrors>>,
+    {
+        if let Some(payload) = self.submitted.lock().unwrap().get(anchor, &content)? {
+            return Ok(payload);
+        }
+        let payload = sign()
+            .await?
+            .as_bytes()
+            .map_err(|_| AoErrors::InvalidTransaction)?;
+        self.submitted
+            .lock()
+            .unwrap()
+            .insert(anchor.to_string(), content, payload)
+    }
+
+    /// Drops the signed item kept for `anchor` by `send_idempotent`
+    pub fn forget_anchor(&self, anchor: &str) {
+        self.submitted.lock().unwrap().remove(anchor);
+    }
+
+    /// Signs the data item carrying `bundle`, without any network I/O
+    pub async fn sign_bundle(&self, bundle: &Bundle) -> Result<SignedMessage, AoErrors> {
+        let item = self
+            .sign_data_item(String::new(), None, bundle.to_bytes()?, Bundle::tags())
+            .await?;
+        Ok(SignedMessage {
+            id: item.id(),
+            bytes: item.to_bytes(),
+        })
+    }
+
+    /// Delivers many messages with a single request to the bundler
+    pub async fn send_bundle(&self, bundle: &Bundle) -> Result<ResponseBundler, AoErrors> {
+        let signed = self.sign_bundle(bundle).await?;
+        self.submit_bundle(signed.bytes).await
+    }
+
+    /// Posts a data item signed by `sign_bundle` to the bundler
+    pub async fn submit_bundle(&self, bytes: Vec<u8>) -> Result<ResponseBundler, AoErrors> {
+        let url = format!("{}/tx", self.bundler_url);
+        let res = self
+            .retry
+            .send(|| {
+                self.client
+                    .post(&url)
+                    .body(bytes.clone())
+                    .headers(Self::octet_stream_headers())
+                    .timeout(self.send_timeout)
+            })
+            .await
+            .map_err(|e| AoErrors::InvalidServerResponse(HttpError::request(&url, e)))?;
+
+        response_json(res).await
+    }
+
+    pub(crate) async fn post_to_mu(&self, payload: Vec<u8>) -> Result<ResponseMu, AoErrors> {
+        // the same signed item is posted on every attempt
+        let res = self
+            .retry
+            .send(|| {
+                self.client
+                    .post(&self.mu_url)
+                    .body(payload.clone())
+                    .headers(Self::octet_stream_headers())
+                    .timeout(self.send_timeout)
+            })
+            .await
+            .map_err(|e| AoErrors::InvalidServerResponse(HttpError::request(&self.mu_url, e)))?;
+
+        response_json(res).await
+    }
 
-        match req {
-            Ok(res) => {
-                let res = res
-                    .text()
-                    .await
-                    .map_err(|_| AoErrors::InvalidServerResponse)?;
+    pub async fn send_and_wait(
+        &self,
+        process_id: String,
+        data: Vec<u8>,
+        tags: Vec<Tag>,
+        wait: WaitConfig,
+    ) -> Result<ResponseCu, AoErrors> {
+        let res = self.send(process_id.clone(), data, tags).await?;
+        self.wait_for_result(process_id, res.id, wait).await
+    }
 
-                serde_json::from_str(&res).map_err(|_| AoErrors::InvalidResponseDeserialization)
+    /// Polls the CU for the result of `message_id` until it is available or
+    /// `wait.timeout` elapses.
+    pub async fn wait_for_result(
+        &self,
+        process_id: String,
+        message_id: String,
+        wait: WaitConfig,
+    ) -> Result<ResponseCu, AoErrors> {
+        let deadline = Instant::now() + wait.timeout;
+        let mut delay = wait.initial_delay;
+        let mut last_error = None;
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
             }
-            Err(e) => Err(AoErrors::InvalidServerResponse),
+
+            let res =
+                tokio::time::timeout(remaining, self.get(process_id.clone(), message_id.clone()))
+                    .await;
+            match res {
+                Ok(Ok(res)) => return Ok(res),
+                // the result may not be available yet, anything else is final
+                Ok(Err(e)) if e.is_retryable() || e.status() == Some(404) => {
+                    last_error = Some(Box::new(e))
+                }
+                Ok(Err(e)) => return Err(e),
+                Err(_) => {}
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            tokio::time::sleep(delay.min(remaining)).await;
+            delay = wait.next_delay(delay);
         }
+
+        Err(AoErrors::ResultTimeout {
+            message_id,
+            last_error,
+        })
     }
 
-    fn get_base_tags(msg_type: String) -> Vec<Tag> {
+    pub(crate) fn get_base_tags(msg_type: String) -> Vec<Tag> {
         vec![
             Tag {
                 name: "Data-Protocol".to_string(),
@@ -154,6 +596,16 @@ impl Legacy {
             .await
     }
 
+    pub async fn eval_and_wait(
+        &self,
+        process_id: String,
+        code: String,
+        wait: WaitConfig,
+    ) -> Result<ResponseCu, AoErrors> {
+        let res = self.eval(process_id.clone(), code).await?;
+        self.wait_for_result(process_id, res.id, wait).await
+    }
+
     pub async fn spawn(
         &self,
         process_name: String,
@@ -162,144 +614,448 @@ impl Legacy {
         scheduler: String,
         tags: Vec<Tag>,
     ) -> Result<ResponseMu, AoErrors> {
-        // Get the current time
-        let now = SystemTime::now();
-        // Get the time since the Unix epoch in nanoseconds
-        let unix_nano = now
+        let mut options = SpawnOptions::new(process_name, app_name)
+            .module(module)
+            .scheduler(scheduler);
+        options.tags = tags;
+        self.spawn_with_options(options).await
+    }
+
+    pub async fn spawn_with_options(&self, options: SpawnOptions) -> Result<ResponseMu, AoErrors> {
+        let data = match &options.data {
+            Some(data) => data.clone(),
+            None => {
+                // Get the current time
+                let now = SystemTime::now();
+                // Get the time since the Unix epoch in nanoseconds
+                let unix_nano = now
+                    .duration_since(UNIX_EPOCH)
+                    .expect("Time went backwards")
+                    .as_nanos();
+                unix_nano.to_string().as_bytes().to_vec()
+            }
+        };
+
+        let mut def_tags = options.to_tags();
+        def_tags.extend(Self::get_base_tags(TYPE_PROCESS.to_string()));
+        def_tags.extend(options.tags);
+
+        self.send("".to_string(), data, def_tags).await
+    }
+
+    /// Spawns a process and returns a handle to it, without waiting for the
+    /// CU to evaluate it.
+    pub async fn spawn_process(&self, options: SpawnOptions) -> Result<Process<'_>, AoErrors> {
+        let res = self.spawn_with_options(options).await?;
+        Ok(Process::new(self, res.id))
+    }
+
+    /// Spawns a process and waits until the CU has evaluated its spawn
+    /// message (and boot code, if any).
+    pub async fn spawn_and_wait(
+        &self,
+        options: SpawnOptions,
+        wait: WaitConfig,
+    ) -> Result<Process<'_>, AoErrors> {
+        let res = self.spawn_with_options(options).await?;
+        self.wait_for_result(res.id.clone(), res.id.clone(), wait)
+            .await?;
+        Ok(Process::new(self, res.id))
+    }
+
+    /// Assigns an existing message (e.g. an Arweave transaction) to a process,
+    /// returning the assignment id. `exclude` lists the message fields the
+    /// process should not load.
+    pub async fn assign(
+        &self,
+        process_id: String,
+        message_id: String,
+        exclude: Vec<String>,
+    ) -> Result<String, AoErrors> {
+        let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .expect("Time went backwards")
             .as_nanos();
-        let unix_nano_str = unix_nano.to_string();
 
-        let base_tags = Self::get_base_tags("Process".to_string());
+        let res = self
+            .send(
+                "".to_string(),
+                now.to_string().as_bytes().to_vec(),
+                Self::get_assignment_tags(process_id, message_id, exclude),
+            )
+            .await?;
+        Ok(res.id)
+    }
 
-        let mut def_tags = vec![
-            Tag {
-                name: "Name".to_string(),
-                value: process_name,
-            },
-            Tag {
-                name: "App-Name".to_string(),
-                value: app_name,
-            },
-            Tag {
-                name: "Module".to_string(),
-                value: module,
-            },
+    fn get_assignment_tags(
+        process_id: String,
+        message_id: String,
+        exclude: Vec<String>,
+    ) -> Vec<Tag> {
+        let mut tags = Self::get_base_tags(TYPE_ASSIGNMENT.to_string());
+        tags.extend(vec![
             Tag {
-                name: "Scheduler".to_string(),
-                value: scheduler,
+                name: "Process".to_string(),
+                value: process_id,
             },
             Tag {
-                name: "Content-Type".to_string(),
-                value: "text/plain".to_string(),
+                name: "Message".to_string(),
+                value: message_id,
             },
-        ];
-        def_tags.extend(base_tags);
-        def_tags.extend(tags);
+        ]);
+        if !exclude.is_empty() {
+            tags.push(Tag {
+                name: "Exclude".to_string(),
+                value: exclude.join(","),
+            });
+        }
+        tags
+    }
 
-        self.send("".to_string(), unix_nano_str.as_bytes().to_vec(), def_tags)
+    /// Spawns a cron process, ticking at `cron.interval` and sending the
+    /// `cron.tags` with every tick. The MU only starts ticking it once
+    /// `monitor` is called.
+    pub async fn spawn_cron(
+        &self,
+        process_name: String,
+        app_name: String,
+        module: String,
+        scheduler: String,
+        cron: Cron,
+        tags: Vec<Tag>,
+    ) -> Result<ResponseMu, AoErrors> {
+        let mut options = SpawnOptions::new(process_name, app_name)
+            .module(module)
+            .scheduler(scheduler)
+            .cron(cron);
+        options.tags = tags;
+        self.spawn_with_options(options).await
+    }
+
+    /// Asks the MU to start ticking the crons of `process_id`
+    pub async fn monitor(&self, process_id: String) -> Result<(), AoErrors> {
+        self.monitor_request(Method::POST, process_id).await
+    }
+
+    /// Asks the MU to stop ticking the crons of `process_id`
+    pub async fn unmonitor(&self, process_id: String) -> Result<(), AoErrors> {
+        self.monitor_request(Method::DELETE, process_id).await
+    }
+
+    async fn monitor_request(&self, method: Method, process_id: String) -> Result<(), AoErrors> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Time went backwards")
+            .as_nanos();
+        let payload = self
+            .sign_message(
+                process_id.clone(),
+                now.to_string().as_bytes().to_vec(),
+                Self::get_base_tags(TYPE_MESSAGE.to_string()),
+            )
+            .await?
+            .bytes;
+
+        let url = format!("{}/monitor/{}", self.mu_url, process_id);
+        let res = self
+            .retry
+            .send(|| {
+                self.client
+                    .request(method.clone(), &url)
+                    .body(payload.clone())
+                    .headers(Self::octet_stream_headers())
+                    .timeout(self.send_timeout)
+            })
+            .await
+            .map_err(|e| AoErrors::InvalidServerResponse(HttpError::request(&url, e)))?;
+
+        response_text(res)
             .await
+            .map_err(AoErrors::InvalidServerResponse)?;
+        Ok(())
+    }
+
+    /// Fetches a page of the results produced by a process cron ticks
+    pub async fn cron_results(
+        &self,
+        process_id: String,
+        query: &ResultsQuery,
+    ) -> Result<ResultsPage, AoErrors> {
+        let res = self
+            .cu_request(&process_id, |cu_url| {
+                self.client
+                    .get(format!("{}/cron/{}", cu_url, process_id))
+                    .query(&query.to_params())
+            })
+            .await?;
+        response_json(res).await
     }
 
+    /// Fetches a message result, failing with `AoErrors::ProcessFailed` when
+    /// its evaluation threw
     pub async fn get(
         &self,
         process_id: String,
         message_id: String,
     ) -> Result<ResponseCu, AoErrors> {
-        let res = reqwest::get(format!(
-            "{}/result/{}?process-id={}",
-            self.cu_url, message_id, process_id
-        ))
-        .await
-        .map_err(|_| AoErrors::InvalidServerResponse)?;
-
-        if res.status().is_redirection() {
-            let new_ao = self.create_ao_from_redirection(&res)?;
-            return Box::pin(async { new_ao.get(process_id, message_id).await }).await;
-        } else {
-            res.json::<ResponseCu>()
-                .await
-                .map_err(|_| AoErrors::InvalidResponseDeserialization)
-        }
+        AoErrors::check_result(self.get_raw(process_id, message_id).await?)
+    }
+
+    /// Fetches a message result as returned by the CU, process errors included
+    pub async fn get_raw(
+        &self,
+        process_id: String,
+        message_id: String,
+    ) -> Result<ResponseCu, AoErrors> {
+        let res = self
+            .cu_request(&process_id, |cu_url| {
+                self.client.get(format!(
+                    "{}/result/{}?process-id={}",
+                    cu_url, message_id, process_id
+                ))
+            })
+            .await?;
+        response_json(res).await
+    }
+
+    /// Fetches a single page of a process results from the CU
+    pub async fn results(
+        &self,
+        process_id: String,
+        query: &ResultsQuery,
+    ) -> Result<ResultsPage, AoErrors> {
+        let res = self
+            .cu_request(&process_id, |cu_url| {
+                self.client
+                    .get(format!("{}/results/{}", cu_url, process_id))
+                    .query(&query.to_params())
+            })
+            .await?;
+        response_json(res).await
+    }
+
+    /// Streams a process results, following the page cursors until the CU
+    /// returns an empty page.
+    pub fn results_stream(
+        &self,
+        process_id: String,
+        query: ResultsQuery,
+    ) -> BoxStream<'_, Result<ResultEdge, AoErrors>> {
+        futures::stream::try_unfold(Some(query), move |query| {
+            let process_id = process_id.clone();
+            async move {
+                let Some(query) = query else {
+                    return Ok(None);
+                };
+                let page = self.results(process_id, &query).await?;
+                let next = page
+                    .edges
+                    .last()
+                    .filter(|edge| query.from.as_ref() != Some(&edge.cursor))
+                    .map(|edge| ResultsQuery {
+                        from: Some(edge.cursor.clone()),
+                        ..query.clone()
+                    });
+                Ok(Some((page.edges, next)))
+            }
+        })
+        .map_ok(|edges| futures::stream::iter(edges.into_iter().map(Ok)))
+        .try_flatten()
+        .boxed()
     }
 
     pub async fn dry_run(
         &self,
         process_id: String,
         data: String,
-        mut tags: Vec<Tag>,
+        tags: Vec<Tag>,
     ) -> Result<ResponseCu, AoErrors> {
-        let original_tags = tags.clone();
-        tags.extend(Self::get_base_tags(TYPE_MESSAGE.to_string()));
-
-        #[derive(Serialize, Deserialize, Debug)]
-        struct Item {
-            pub Id: String,
-            pub Target: String,
-            pub Owner: String,
-            pub Data: String,
-            pub Tags: Vec<Tag>,
-            pub Anchor: Option<String>, // Anchor is optional in Go struct
-        }
+        let mut request = DryRunRequest::new(process_id).data_text(data);
+        request.tags = tags;
+        self.dry_run_request(request).await
+    }
 
-        let res = Client::new()
-            .post(format!(
-                "{}/dry-run?process-id={}",
-                &self.cu_url, process_id
-            ))
-            .json(&Item {
-                Id: "0000000000000000000000000000000000000000001".to_string(),
-                Target: process_id.clone(),
-                Owner: "0000000000000000000000000000000000000000001".to_string(),
-                Data: data.clone(),
-                Tags: tags,
-                Anchor: None,
-            })
-            .send()
-            .await
-            .map_err(|_| AoErrors::InvalidServerResponse)?;
-
-        if res.status().is_redirection() {
-            let new_ao = self.create_ao_from_redirection(&res)?;
-            return Box::pin(async {
-                new_ao
-                    .dry_run(process_id, data.clone(), original_tags)
-                    .await
+    /// Evaluates a message on the CU without sending it, letting the caller
+    /// pick the `Owner`, `From`, `Id`, `Anchor` and data of the message.
+    /// Fails with `AoErrors::ProcessFailed` when the evaluation threw.
+    pub async fn dry_run_request(&self, request: DryRunRequest) -> Result<ResponseCu, AoErrors> {
+        let mut item = request.clone();
+        item.tags
+            .extend(Self::get_base_tags(TYPE_MESSAGE.to_string()));
+
+        let res = self
+            .cu_request(&request.target, |cu_url| {
+                self.client
+                    .post(format!(
+                        "{}/dry-run?process-id={}",
+                        cu_url, request.target
+                    ))
+                    .json(&item)
             })
-            .await;
-        } else {
-            res.json::<ResponseCu>()
-                .await
-                .map_err(|_| AoErrors::InvalidResponseDeserialization)
+            .await?;
+        AoErrors::check_result(response_json(res).await?)
+    }
+
+    /// The CU the requests of `process_id` are sent to
+    pub fn cu_url_for(&self, process_id: &str) -> String {
+        self.cu_routes
+            .lock()
+            .unwrap()
+            .get(process_id)
+            .cloned()
+            .unwrap_or_else(|| self.cu_url.clone())
+    }
+
+    // Sends the request built for a CU url, following the router redirects
+    // up to `max_redirects` times. The CU a process ends up on is cached,
+    // and dropped again when it can't be reached.
+    async fn cu_request<F>(&self, process_id: &str, request: F) -> Result<Response, AoErrors>
+    where
+        F: Fn(&str) -> RequestBuilder,
+    {
+        let mut cu_url = self.cu_url_for(process_id);
+
+        for _ in 0..=self.max_redirects {
+            let res = match self.retry.send(|| request(&cu_url)).await {
+                Ok(res) => res,
+                Err(e) => {
+                    self.cu_routes.lock().unwrap().remove(process_id);
+                    return Err(AoErrors::InvalidServerResponse(HttpError::request(&cu_url, e)));
+                }
+            };
+
+            if !res.status().is_redirection() {
+                if cu_url != self.cu_url {
+                    self.cu_routes
+                        .lock()
+                        .unwrap()
+                        .insert(process_id.to_string(), cu_url);
+                }
+                return Ok(res);
+            }
+            cu_url = redirect_cu_url(&cu_url, res.url(), res.headers().get(LOCATION))?;
         }
+
+        Err(AoErrors::TooManyRedirects(self.max_redirects))
     }
+}
 
-    fn create_ao_from_redirection(&self, res: &Response) -> Result<Legacy, AoErrors> {
-        let new_ao = Self::new(
-            "".to_string(),
-            res.headers()
-                .get("Location")
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string(),
-            self.signer_type.clone(),
-        )?;
-        Ok(new_ao)
+// Signed items of the idempotent sends, least recently used first
+struct SubmittedItems {
+    capacity: usize,
+    items: VecDeque<SubmittedItem>,
+}
+
+struct SubmittedItem {
+    anchor: String,
+    content: [u8; 32],
+    payload: Vec<u8>,
+}
+
+impl SubmittedItems {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            items: VecDeque::new(),
+        }
+    }
+
+    fn get(&mut self, anchor: &str, content: &[u8; 32]) -> Result<Option<Vec<u8>>, AoErrors> {
+        let Some(index) = self.items.iter().position(|item| item.anchor == anchor) else {
+            return Ok(None);
+        };
+        let item = self.items.remove(index).unwrap();
+        if item.content != *content {
+            let anchor = item.anchor.clone();
+            self.items.push_back(item);
+            return Err(AoErrors::AnchorReused(anchor));
+        }
+        let payload = item.payload.clone();
+        self.items.push_back(item);
+        Ok(Some(payload))
+    }
+
+    // an item signed concurrently for the same anchor wins
+    fn insert(
+        &mut self,
+        anchor: String,
+        content: [u8; 32],
+        payload: Vec<u8>,
+    ) -> Result<Vec<u8>, AoErrors> {
+        if let Some(payload) = self.get(&anchor, &content)? {
+            return Ok(payload);
+        }
+        self.items.push_back(SubmittedItem {
+            anchor,
+            content,
+            payload: payload.clone(),
+        });
+        while self.items.len() > self.capacity {
+            self.items.pop_front();
+        }
+        Ok(payload)
+    }
+
+    fn remove(&mut self, anchor: &str) {
+        self.items.retain(|item| item.anchor != anchor);
     }
 }
 
+// The CU router answers with the URL of the same request on the target CU,
+// so the target base url is the Location minus the path of the request
+// relative to `cu_url`. A bare origin is accepted as well.
+fn redirect_cu_url(
+    cu_url: &str,
+    request_url: &Url,
+    location: Option<&HeaderValue>,
+) -> Result<String, AoErrors> {
+    let location = location
+        .and_then(|location| location.to_str().ok())
+        .ok_or(AoErrors::InvalidRedirect)?;
+    let location = request_url
+        .join(location)
+        .map_err(|_| AoErrors::InvalidRedirect)?;
+    if !matches!(location.scheme(), "http" | "https") {
+        return Err(AoErrors::InvalidRedirect);
+    }
+
+    let base_path = Url::parse(cu_url)
+        .map(|url| url.path().trim_end_matches('/').to_string())
+        .unwrap_or_default();
+    let route = request_url
+        .path()
+        .strip_prefix(base_path.as_str())
+        .unwrap_or(request_url.path());
+    let path = location.path().strip_suffix(route).unwrap_or_default();
+
+    Ok(format!(
+        "{}{}",
+        location.origin().ascii_serialization(),
+        path.trim_end_matches('/')
+    ))
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::ao::Legacy;
+    use crate::ao::{redirect_cu_url, Legacy, LegacyBuilder, WaitConfig, DEFAULT_SEND_TIMEOUT};
+    use crate::data_item::DataItem;
+    use crate::errors::AoErrors;
     use crate::scheme::{DEFAULT_MODULE, DEFAULT_SCHEDULER};
-    use crate::wallet::SignerTypes;
+    use crate::wallet::{EthereumKey, SignerTypes};
     use crate::scheme::Tag;
+    use crate::testing::{MockResponse, MockServer};
+    use crate::utils::random_anchor;
+    use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use bundlr_sdk::BundlrTx;
+    use reqwest::header::HeaderValue;
+    use reqwest::{Method, Url};
+    use sha2::{Digest, Sha256};
+    use std::time::Duration;
 
     #[tokio::test]
     pub async fn test_init() {
-        let ao = Legacy::new(
+        let _ao = Legacy::new(
             "https://mu.ao-testnet.xyz".to_string(),
             "https://cu.ao-testnet.xyz".to_string(),
             SignerTypes::Arweave("test_key.json".to_string()),
@@ -307,16 +1063,40 @@ mod tests {
         .unwrap();
     }
 
+    #[tokio::test]
+    pub async fn test_builder() {
+        let ao = Legacy::builder(SignerTypes::Arweave("test_key.json".to_string()))
+            .mu_url("https://mu.ao-testnet.xyz".to_string())
+            .timeout(Duration::from_secs(30))
+            .user_agent("indexer/1.0".to_string())
+            .build()
+            .unwrap();
+        assert_eq!(ao.send_timeout, DEFAULT_SEND_TIMEOUT);
+
+        let shared = ao.client().clone();
+        let _ao = Legacy::builder(SignerTypes::Arweave("test_key.json".to_string()))
+            .client(shared)
+            .send_timeout(Duration::from_secs(5))
+            .build()
+            .unwrap();
+    }
+
     #[tokio::test]
     pub async fn test_default_init() {
-        let ao = Legacy::default_init(SignerTypes::Arweave("test_key.json".to_string())).unwrap();
+        let _ao = Legacy::default_init(SignerTypes::Arweave("test_key.json".to_string())).unwrap();
     }
 
     #[tokio::test]
     pub async fn test_result() {
+        let mock = MockServer::start().await.unwrap();
+        mock.set_result(
+            "ya9XinY0qXeYyf7HXANqzOiKns8yiXZoDtFqUMXkX0Q",
+            "5JtjkYy1hk0Zce5mP6gDWIOdt9rCSQAFX-K9jZnqniw",
+            serde_json::json!({"Output": {"data": "1984"}, "Messages": [], "GasUsed": 0}),
+        );
         let ao = Legacy::new(
-            "https://mu.ao-testnet.xyz".to_string(),
-            "https://cu.ao-testnet.xyz".to_string(),
+            mock.url(),
+            mock.url(),
             SignerTypes::Arweave("test_key.json".to_string()),
         )
         .unwrap();
@@ -333,7 +1113,8 @@ mod tests {
 
     #[tokio::test]
     pub async fn test_spawn() {
-        let ao = Legacy::default_init(SignerTypes::Arweave("test_key.json".to_string())).unwrap();
+        let mock = MockServer::start().await.unwrap();
+        let ao = mock.legacy(SignerTypes::Arweave("test_key.json".to_string()));
         let res = ao
             .spawn(
                 "test1".to_string(),
@@ -345,14 +1126,18 @@ mod tests {
             .await;
         println!("{:?}", res);
         assert!(res.is_ok());
+        let item = &mock.received_items()[0];
+        assert_eq!(item.tag("Type"), Some("Process"));
+        assert_eq!(item.tag("Module"), Some(DEFAULT_MODULE));
         println!("{}", serde_json::to_string(&res.unwrap()).unwrap())
     }
 
     #[tokio::test]
     pub async fn test_dry_run() {
+        let mock = MockServer::start().await.unwrap();
         let ao = Legacy::new(
-            "https://mu.ao-testnet.xyz".to_string(),
-            "https://cu.ao-testnet.xyz".to_string(),
+            mock.url(),
+            mock.url(),
             SignerTypes::Arweave("test_key.json".to_string()),
         )
         .unwrap();
@@ -367,6 +1152,340 @@ mod tests {
             )
             .await;
         assert!(res.is_ok());
+        let request = &mock.requests()[0];
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert!(body["Tags"].to_string().contains("Info"));
         println!("{}", serde_json::to_string(&res.unwrap()).unwrap())
     }
+
+    #[test]
+    pub fn test_assignment_tags() {
+        let tags = Legacy::get_assignment_tags(
+            "ya9XinY0qXeYyf7HXANqzOiKns8yiXZoDtFqUMXkX0Q".to_string(),
+            "5JtjkYy1hk0Zce5mP6gDWIOdt9rCSQAFX-K9jZnqniw".to_string(),
+            vec!["Data".to_string(), "Anchor".to_string()],
+        );
+        let tag = |name: &str| tags.iter().find(|t| t.name == name).map(|t| t.value.clone());
+        assert_eq!(tag("Type").as_deref(), Some("Assignment"));
+        assert_eq!(
+            tag("Process").as_deref(),
+            Some("ya9XinY0qXeYyf7HXANqzOiKns8yiXZoDtFqUMXkX0Q")
+        );
+        assert_eq!(
+            tag("Message").as_deref(),
+            Some("5JtjkYy1hk0Zce5mP6gDWIOdt9rCSQAFX-K9jZnqniw")
+        );
+        assert_eq!(tag("Exclude").as_deref(), Some("Data,Anchor"));
+    }
+
+    #[tokio::test]
+    pub async fn test_idempotent_item_reused() {
+        let ao = Legacy::builder(SignerTypes::Arweave("test_key.json".to_string()))
+            .idempotency_capacity(2)
+            .build()
+            .unwrap();
+        let process_id = "xU9zFkq3X2ZQ6olwNVvr1vUWIjc3kXTWr7xKQD6dh10";
+        let anchor = random_anchor();
+        let content = Legacy::content_hash(process_id, b"hello", &[]);
+        let sign = || {
+            ao.sign_bundle_item(
+                process_id.to_string(),
+                Some(&anchor),
+                b"hello".to_vec(),
+                vec![],
+            )
+        };
+
+        let first = ao.idempotent_item(&anchor, content, sign).await.unwrap();
+        // RSA-PSS signatures are randomized, equal bytes mean the item was reused
+        let retry = ao.idempotent_item(&anchor, content, sign).await.unwrap();
+        assert_eq!(first, retry);
+
+        // the same anchor for other data is an error, not the first item
+        let other = Legacy::content_hash(process_id, b"other", &[]);
+        let res = ao.idempotent_item(&anchor, other, sign).await;
+        assert!(matches!(res, Err(AoErrors::AnchorReused(a)) if a == anchor));
+
+        ao.forget_anchor(&anchor);
+        let resigned = ao.idempotent_item(&anchor, content, sign).await.unwrap();
+        assert_ne!(first, resigned);
+
+        // beyond the capacity the least recently used item is dropped
+        for _ in 0..2 {
+            let anchor = random_anchor();
+            ao.idempotent_item(&anchor, content, || {
+                ao.sign_bundle_item(
+                    process_id.to_string(),
+                    Some(&anchor),
+                    b"hello".to_vec(),
+                    vec![],
+                )
+            })
+            .await
+            .unwrap();
+        }
+        let evicted = ao.idempotent_item(&anchor, content, sign).await.unwrap();
+        assert_ne!(resigned, evicted);
+    }
+
+    #[tokio::test]
+    pub async fn test_send_with_invalid_anchor() {
+        let ao = Legacy::new(
+            "http://127.0.0.1:9".to_string(),
+            "http://127.0.0.1:9".to_string(),
+            SignerTypes::Arweave("test_key.json".to_string()),
+        )
+        .unwrap();
+        let process_id = "xU9zFkq3X2ZQ6olwNVvr1vUWIjc3kXTWr7xKQD6dh10".to_string();
+
+        for anchor in ["abc".to_string(), "a".repeat(33)] {
+            let res = ao
+                .send_with_anchor(process_id.clone(), b"hello".to_vec(), vec![], &anchor)
+                .await;
+            assert!(matches!(res, Err(AoErrors::InvalidMessage(_))));
+            let res = ao
+                .send_idempotent(process_id.clone(), b"hello".to_vec(), vec![], &anchor)
+                .await;
+            assert!(matches!(res, Err(AoErrors::InvalidMessage(_))));
+        }
+
+        let res = ao
+            .send(BASE64_URL_SAFE_NO_PAD.encode(b"short"), b"hello".to_vec(), vec![])
+            .await;
+        assert!(matches!(res, Err(AoErrors::InvalidMessage(_))));
+    }
+
+    #[tokio::test]
+    pub async fn test_ethereum_signer() {
+        let key = EthereumKey::Hex(
+            "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318".to_string(),
+        );
+        let ao = Legacy::default_init(SignerTypes::Ethereum(key)).unwrap();
+        for anchor in [None, Some(random_anchor())] {
+            let mut tx = ao
+                .sign_bundle_item(
+                    "xU9zFkq3X2ZQ6olwNVvr1vUWIjc3kXTWr7xKQD6dh10".to_string(),
+                    anchor.as_deref(),
+                    b"hello".to_vec(),
+                    vec![Tag::new("Action", "Ping")],
+                )
+                .await
+                .unwrap();
+            assert!(tx.verify().await.is_ok());
+            let bytes = tx.as_bytes().unwrap();
+            assert_eq!(bytes[..2], 3u16.to_le_bytes());
+            // generated anchors are printable base64url, like `random_anchor`
+            let item = DataItem::from_bytes(&bytes).unwrap();
+            let anchor = item.anchor().unwrap();
+            assert!(BASE64_URL_SAFE_NO_PAD.decode(anchor).is_ok());
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_address() {
+        let ao = Legacy::default_init(SignerTypes::Arweave("test_key.json".to_string())).unwrap();
+        assert_eq!(ao.address(), "eIgnDk4vSKPe0lYB6yhCHDV1dOw3JgYHGocfj7WGrjQ");
+        assert_eq!(ao.owner().len(), 512);
+
+        let key = EthereumKey::Hex(
+            "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318".to_string(),
+        );
+        let ao = Legacy::default_init(SignerTypes::Ethereum(key)).unwrap();
+        assert_eq!(ao.address(), "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23");
+    }
+
+    #[tokio::test]
+    pub async fn test_monitor() {
+        let mock = MockServer::start().await.unwrap();
+        let ao = mock.legacy(SignerTypes::Arweave("test_key.json".to_string()));
+        let process_id = "xU9zFkq3X2ZQ6olwNVvr1vUWIjc3kXTWr7xKQD6dh10";
+
+        ao.monitor(process_id.to_string()).await.unwrap();
+        let request = &mock.requests()[0];
+        assert_eq!(request.path, format!("/monitor/{}", process_id));
+        let item = DataItem::from_bytes(&request.body).unwrap();
+        assert_eq!(item.target().as_deref(), Some(process_id));
+        assert_eq!(item.tag("Data-Protocol"), Some("ao"));
+        assert_eq!(item.tag("Type"), Some("Message"));
+
+        let path = format!("/monitor/{}", process_id);
+        mock.respond(Method::DELETE, &path, MockResponse::new(400));
+        match ao.unmonitor(process_id.to_string()).await {
+            Err(AoErrors::InvalidServerResponse(e)) => {
+                assert_eq!(e.url, format!("{}{}", mock.url(), path));
+                assert_eq!(e.status, Some(400));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    pub fn test_raw_signer() {
+        let ao = Legacy::default_init(SignerTypes::Arweave("test_key.json".to_string())).unwrap();
+        assert_eq!(ao.raw_signer().unwrap().pub_key().to_vec(), ao.owner());
+
+        let custom = LegacyBuilder::with_signer(ao.signer.clone()).build().unwrap();
+        assert!(matches!(custom.raw_signer(), Err(AoErrors::InvalidSigner { .. })));
+    }
+
+    #[test]
+    pub fn test_wait_config_backoff() {
+        let wait = WaitConfig::default();
+        let delay = wait.next_delay(wait.initial_delay);
+        assert_eq!(delay, Duration::from_secs(1));
+        assert_eq!(wait.next_delay(Duration::from_secs(4)), wait.max_delay);
+    }
+
+    #[tokio::test]
+    pub async fn test_wait_for_result_timeout() {
+        let ao = Legacy::new(
+            "http://127.0.0.1:9".to_string(),
+            "http://127.0.0.1:9".to_string(),
+            SignerTypes::Arweave("test_key.json".to_string()),
+        )
+        .unwrap();
+        let wait = WaitConfig {
+            initial_delay: Duration::from_millis(10),
+            timeout: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let res = ao
+            .wait_for_result("process".to_string(), "message".to_string(), wait)
+            .await;
+        // the message was sent, retrying the send would duplicate it
+        assert!(!res.as_ref().unwrap_err().is_retryable());
+        match res {
+            Err(AoErrors::ResultTimeout {
+                message_id,
+                last_error,
+            }) => {
+                assert_eq!(message_id, "message");
+                assert!(matches!(
+                    last_error.as_deref(),
+                    Some(AoErrors::InvalidServerResponse(_))
+                ));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_wait_for_result_permanent_error() {
+        let mock = MockServer::start().await.unwrap();
+        mock.respond(
+            Method::GET,
+            "/result/message",
+            MockResponse::new(400).body("invalid process id"),
+        );
+        let ao = mock.legacy(SignerTypes::Arweave("test_key.json".to_string()));
+        let wait = WaitConfig {
+            initial_delay: Duration::from_millis(10),
+            timeout: Duration::from_secs(30),
+            ..Default::default()
+        };
+
+        let started = std::time::Instant::now();
+        let res = ao
+            .wait_for_result("process".to_string(), "message".to_string(), wait)
+            .await;
+        assert!(matches!(res, Err(AoErrors::InvalidServerResponse(_))));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    pub fn test_redirect_cu_url() {
+        let request = Url::parse("https://cu.ao-testnet.xyz/result/msg?process-id=pid").unwrap();
+        let location = HeaderValue::from_static("https://cu24.ao-testnet.xyz/result/msg?process-id=pid");
+        assert_eq!(
+            redirect_cu_url("https://cu.ao-testnet.xyz", &request, Some(&location)).unwrap(),
+            "https://cu24.ao-testnet.xyz"
+        );
+
+        let location = HeaderValue::from_static("https://cu24.ao-testnet.xyz");
+        assert_eq!(
+            redirect_cu_url("https://cu.ao-testnet.xyz", &request, Some(&location)).unwrap(),
+            "https://cu24.ao-testnet.xyz"
+        );
+
+        let request = Url::parse("https://ao.example/cu/dry-run?process-id=pid").unwrap();
+        let location = HeaderValue::from_static("/cu-2/dry-run?process-id=pid");
+        assert_eq!(
+            redirect_cu_url("https://ao.example/cu", &request, Some(&location)).unwrap(),
+            "https://ao.example/cu-2"
+        );
+
+        assert!(matches!(
+            redirect_cu_url("https://ao.example/cu", &request, None),
+            Err(AoErrors::InvalidRedirect)
+        ));
+        let location = HeaderValue::from_static("ftp://ao.example");
+        assert!(matches!(
+            redirect_cu_url("https://ao.example/cu", &request, Some(&location)),
+            Err(AoErrors::InvalidRedirect)
+        ));
+    }
+
+    #[tokio::test]
+    pub async fn test_cu_redirect_cache() {
+        let router = MockServer::start().await.unwrap();
+        let cu = MockServer::start().await.unwrap();
+        router.redirect_process("pid", &cu.url());
+
+        // the router answers once, the second request must go straight to the CU
+        let ao = router.legacy(SignerTypes::Arweave("test_key.json".to_string()));
+        for _ in 0..2 {
+            ao.get("pid".to_string(), "msg".to_string()).await.unwrap();
+            assert_eq!(ao.cu_url_for("pid"), cu.url());
+        }
+        assert_eq!(router.requests().len(), 1);
+        assert_eq!(cu.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    pub async fn test_sign_message_offline() {
+        let ao = Legacy::default_init(SignerTypes::Arweave("test_key.json".to_string())).unwrap();
+        let message = ao
+            .sign_message(
+                "xU9zFkq3X2ZQ6olwNVvr1vUWIjc3kXTWr7xKQD6dh10".to_string(),
+                b"ping".to_vec(),
+                vec![Tag::new("Action", "Ping")],
+            )
+            .await
+            .unwrap();
+
+        let mut tx = BundlrTx::from_bytes(message.bytes.clone()).unwrap();
+        assert!(tx.verify().await.is_ok());
+        assert_eq!(
+            message.id,
+            BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(tx.get_signarure()))
+        );
+        assert_eq!(message.id.len(), 43);
+    }
+
+    #[tokio::test]
+    pub async fn test_submit_signed() {
+        let mu = MockServer::start().await.unwrap();
+
+        let signer = Legacy::default_init(SignerTypes::Arweave("test_key.json".to_string())).unwrap();
+        let message = signer
+            .sign_message(
+                "xU9zFkq3X2ZQ6olwNVvr1vUWIjc3kXTWr7xKQD6dh10".to_string(),
+                b"ping".to_vec(),
+                vec![],
+            )
+            .await
+            .unwrap();
+
+        let ao = mu.legacy(SignerTypes::Arweave("test_key.json".to_string()));
+        let res = ao.submit_signed(message.bytes.clone()).await.unwrap();
+        assert_eq!(res.id, message.id);
+        assert_eq!(mu.requests()[0].body, message.bytes);
+        assert_eq!(mu.received_items()[0].id(), message.id);
+
+        assert!(matches!(
+            ao.submit_signed(b"not a data item".to_vec()).await,
+            Err(AoErrors::InvalidDataItem(_))
+        ));
+    }
 }
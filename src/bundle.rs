@@ -0,0 +1,179 @@
+use crate::data_item::DataItem;
+use crate::errors::AoErrors;
+use crate::scheme::{SignedMessage, Tag};
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use base64::Engine;
+
+pub const BUNDLE_FORMAT: &str = "binary";
+pub const BUNDLE_VERSION: &str = "2.0.0";
+
+/// An ANS-104 bundle: a 32 bytes item count, then a 64 bytes header (size
+/// and id) per item, then the items themselves, all integers being little
+/// endian
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Bundle {
+    pub items: Vec<DataItem>,
+}
+
+impl Bundle {
+    pub fn new(items: Vec<DataItem>) -> Self {
+        Self { items }
+    }
+
+    /// Bundles messages signed by `Legacy::sign_message`
+    pub fn from_messages(messages: &[SignedMessage]) -> Result<Self, AoErrors> {
+        let items = messages
+            .iter()
+            .map(|message| DataItem::from_bytes(&message.bytes))
+            .collect::<Result<_, _>>()?;
+        Ok(Self::new(items))
+    }
+
+    pub fn push(&mut self, item: DataItem) {
+        self.items.push(item);
+    }
+
+    /// The tags of the data item carrying a bundle
+    pub fn tags() -> Vec<Tag> {
+        vec![
+            Tag::new("Bundle-Format", BUNDLE_FORMAT),
+            Tag::new("Bundle-Version", BUNDLE_VERSION),
+        ]
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, AoErrors> {
+        let items: Vec<Vec<u8>> = self.items.iter().map(DataItem::to_bytes).collect();
+
+        let mut buffer = Vec::with_capacity(32 + 64 * items.len());
+        buffer.extend_from_slice(&u256(items.len()));
+        for (item, bytes) in self.items.iter().zip(&items) {
+            let id = BASE64_URL_SAFE_NO_PAD
+                .decode(item.id())
+                .map_err(|_| AoErrors::Base64ReadingError)?;
+            buffer.extend_from_slice(&u256(bytes.len()));
+            buffer.extend_from_slice(&id);
+        }
+        for bytes in items {
+            buffer.extend_from_slice(&bytes);
+        }
+        Ok(buffer)
+    }
+
+    /// Unpacks a bundle, checking that every item matches the id of its
+    /// header. The signatures are left to `verify`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, AoErrors> {
+        let invalid = |reason: &str| AoErrors::InvalidBundle(reason.to_string());
+
+        let count = bytes
+            .get(..32)
+            .and_then(read_u256)
+            .ok_or_else(|| invalid("invalid item count"))?;
+        let headers_end = count
+            .checked_mul(64)
+            .and_then(|length| length.checked_add(32))
+            .filter(|end| *end <= bytes.len())
+            .ok_or_else(|| invalid("the headers exceed the bundle"))?;
+
+        let mut offset = headers_end;
+        let mut items = Vec::with_capacity(count);
+        for header in bytes[32..headers_end].chunks_exact(64) {
+            let size = read_u256(&header[..32]).ok_or_else(|| invalid("invalid item size"))?;
+            let end = offset
+                .checked_add(size)
+                .filter(|end| *end <= bytes.len())
+                .ok_or_else(|| invalid("an item exceeds the bundle"))?;
+
+            let item = DataItem::from_bytes(&bytes[offset..end])?;
+            if item.id() != BASE64_URL_SAFE_NO_PAD.encode(&header[32..]) {
+                return Err(invalid("an item does not match its header id"));
+            }
+            items.push(item);
+            offset = end;
+        }
+
+        Ok(Self::new(items))
+    }
+
+    /// Checks the signature of every item, see `DataItem::verify`
+    pub fn verify(&self) -> Result<(), AoErrors> {
+        self.items.iter().try_for_each(DataItem::verify)
+    }
+}
+
+fn u256(value: usize) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes[..8].copy_from_slice(&(value as u64).to_le_bytes());
+    bytes
+}
+
+// sizes and counts beyond usize can't be held in memory anyway
+fn read_u256(bytes: &[u8]) -> Option<usize> {
+    let (low, high) = bytes.split_at(8);
+    if high.iter().any(|b| *b != 0) {
+        return None;
+    }
+    usize::try_from(u64::from_le_bytes(low.try_into().ok()?)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ao::Legacy;
+    use crate::bundle::Bundle;
+    use crate::data_item::DataItem;
+    use crate::errors::AoErrors;
+    use crate::scheme::Tag;
+    use crate::wallet::SignerTypes;
+
+    #[tokio::test]
+    pub async fn test_bundle_roundtrip() {
+        let ao = Legacy::default_init(SignerTypes::Arweave("test_key.json".to_string())).unwrap();
+        let mut messages = vec![];
+        for i in 0..3 {
+            let message = ao
+                .sign_message(
+                    "xU9zFkq3X2ZQ6olwNVvr1vUWIjc3kXTWr7xKQD6dh10".to_string(),
+                    format!("ping {}", i).into_bytes(),
+                    vec![Tag::new("Action", "Ping")],
+                )
+                .await
+                .unwrap();
+            messages.push(message);
+        }
+
+        let bundle = Bundle::from_messages(&messages).unwrap();
+        let bytes = bundle.to_bytes().unwrap();
+        assert_eq!(bytes[0], 3);
+        assert_eq!(
+            bytes.len(),
+            32 + 3 * 64 + messages.iter().map(|m| m.bytes.len()).sum::<usize>()
+        );
+
+        let unpacked = Bundle::from_bytes(&bytes).unwrap();
+        assert!(unpacked.verify().is_ok());
+        assert_eq!(unpacked, bundle);
+        let ids: Vec<String> = unpacked.items.iter().map(DataItem::id).collect();
+        let expected: Vec<String> = messages.iter().map(|m| m.id.clone()).collect();
+        assert_eq!(ids, expected);
+
+        // the bundle carrying data item
+        let signed = ao.sign_bundle(&bundle).await.unwrap();
+        let item = DataItem::from_bytes(&signed.bytes).unwrap();
+        assert!(item.verify().is_ok());
+        assert_eq!(item.target(), None);
+        assert_eq!(item.tag("Bundle-Format"), Some("binary"));
+        assert_eq!(Bundle::from_bytes(item.data()).unwrap(), bundle);
+
+        // a header id swapped with the next one
+        let mut swapped = bytes.clone();
+        swapped.copy_within(32 + 64 + 32..32 + 128, 32 + 32);
+        assert!(matches!(
+            Bundle::from_bytes(&swapped),
+            Err(AoErrors::InvalidBundle(_))
+        ));
+        assert!(matches!(
+            Bundle::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(AoErrors::InvalidBundle(_))
+        ));
+        assert_eq!(Bundle::from_bytes(&[0u8; 32]).unwrap().items.len(), 0);
+    }
+}
@@ -0,0 +1,573 @@
+use crate::ao::Legacy;
+use crate::data_item::DataItem;
+use crate::hyperbeam::Hyperbeam;
+use crate::scheme::{DEFAULT_CU, DEFAULT_MU, HB_NODE_ENDPOINT, TYPE_ASSIGNMENT, TYPE_PROCESS};
+use crate::wallet::SignerTypes;
+use axum::body::{Body, Bytes};
+use axum::extract::State;
+use axum::http::{HeaderMap, Method, Uri};
+use axum::response::Response;
+use axum::Router;
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Requests are forwarded to the live hosts and the exchanges recorded
+    Record,
+    /// Requests are answered from the recorded exchanges, without network
+    Replay,
+}
+
+/// A recorded request and the response it got
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// Proxied path and query, starting with the scheme and host of the
+    /// live server, e.g. `/https/cu.ao-testnet.xyz/result/...`
+    pub path: String,
+    /// The body as normalized by `normalize_body`
+    pub body: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecordedResponse {
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    pub body: String,
+    /// Whether `body` is base64, for responses which aren't UTF-8
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub base64: bool,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Fixture {
+    interactions: Vec<Interaction>,
+}
+
+struct Shared {
+    mode: CassetteMode,
+    client: reqwest::Client,
+    interactions: Mutex<Vec<Interaction>>,
+    // interactions already replayed
+    used: Mutex<Vec<bool>>,
+}
+
+/// A VCR-style HTTP recorder for `Legacy` and `Hyperbeam`, running as an
+/// in-process proxy.
+///
+/// Clients are pointed at the proxy with `proxy` (or `legacy` and
+/// `hyperbeam`). In `Record` mode their requests are forwarded to the live
+/// server and saved to the fixture file by `save`; in `Replay` mode they are
+/// answered from the fixture, matching the method, path and normalized body.
+/// Identical requests are replayed in the order they were recorded, the last
+/// response being repeated once they are used up (e.g. while polling).
+///
+/// CU redirects are followed while recording, so replayed results never
+/// redirect.
+pub struct Cassette {
+    url: String,
+    path: PathBuf,
+    shared: Arc<Shared>,
+    server: JoinHandle<()>,
+}
+
+impl Cassette {
+    pub async fn record(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Self::start(path.as_ref(), CassetteMode::Record, Fixture::default()).await
+    }
+
+    pub async fn replay(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let fixture = serde_json::from_slice(&std::fs::read(path.as_ref())?)?;
+        Self::start(path.as_ref(), CassetteMode::Replay, fixture).await
+    }
+
+    /// Replays the fixture at `path`, or records it when it doesn't exist
+    pub async fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        if path.as_ref().exists() {
+            Self::replay(path).await
+        } else {
+            Self::record(path).await
+        }
+    }
+
+    async fn start(path: &Path, mode: CassetteMode, fixture: Fixture) -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let shared = Arc::new(Shared {
+            mode,
+            client: reqwest::Client::new(),
+            used: Mutex::new(vec![false; fixture.interactions.len()]),
+            interactions: Mutex::new(fixture.interactions),
+        });
+
+        let app = Router::new().fallback(handle).with_state(shared.clone());
+        let server = tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+
+        Ok(Self {
+            url,
+            path: path.to_path_buf(),
+            shared,
+            server,
+        })
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.shared.mode
+    }
+
+    /// The proxy url standing for the live server `url`, which must be an
+    /// absolute http(s) url
+    pub fn proxy(&self, url: &str) -> std::io::Result<String> {
+        let invalid = |reason: &str| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("invalid live url {}: {}", url, reason),
+            )
+        };
+        let parsed = Url::parse(url).map_err(|e| invalid(&e.to_string()))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(invalid("not an http url"));
+        }
+        let host = parsed.host_str().ok_or_else(|| invalid("no host"))?;
+        let host = match parsed.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_string(),
+        };
+        Ok(format!(
+            "{}/{}/{}{}",
+            self.url,
+            parsed.scheme(),
+            host,
+            parsed.path().trim_end_matches('/')
+        ))
+    }
+
+    /// A `Legacy` client using the default MU and CU through the proxy
+    pub fn legacy(&self, signer: SignerTypes) -> Legacy {
+        Legacy::builder(signer)
+            .mu_url(self.proxy(DEFAULT_MU).unwrap())
+            .cu_url(self.proxy(DEFAULT_CU).unwrap())
+            .build()
+            .unwrap()
+    }
+
+    /// A `Hyperbeam` client using the default node through the proxy
+    pub fn hyperbeam(&self, signer: SignerTypes) -> Hyperbeam {
+        Hyperbeam::new(self.proxy(HB_NODE_ENDPOINT).unwrap(), signer).unwrap()
+    }
+
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.shared.interactions.lock().unwrap().clone()
+    }
+
+    /// Writes the recorded interactions to the fixture file
+    pub fn save(&self) -> std::io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let fixture = Fixture {
+            interactions: self.interactions(),
+        };
+        std::fs::write(&self.path, serde_json::to_vec_pretty(&fixture)?)
+    }
+}
+
+impl Drop for Cassette {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+/// The body a request is matched on. Signed data items are reduced to their
+/// target, tags and data, as their signature and anchor change on every
+/// run, and JSON bodies get sorted keys. The data of spawns without data and
+/// of assignments, which is the current time, is left out.
+pub fn normalize_body(body: &[u8]) -> String {
+    if body.is_empty() {
+        return String::new();
+    }
+    if let Ok(item) = DataItem::from_bytes(body) {
+        return normalize_item(&item, is_timestamp_data(&item));
+    }
+    match serde_json::from_slice::<Value>(body) {
+        Ok(value) => sort_keys(value).to_string(),
+        Err(_) => String::from_utf8_lossy(body).into_owned(),
+    }
+}
+
+fn normalize_item(item: &DataItem, skip_data: bool) -> String {
+    let data = match skip_data {
+        true => Value::Null,
+        false => Value::from(String::from_utf8_lossy(item.data())),
+    };
+    json!({
+        "target": item.target(),
+        "tags": item.tags(),
+        "data": data,
+    })
+    .to_string()
+}
+
+// monitor requests are signed over the time too, whatever their tags
+fn normalize_request_body(path: &str, body: &[u8]) -> String {
+    let monitor = path.split('/').rev().nth(1) == Some("monitor");
+    match DataItem::from_bytes(body) {
+        Ok(item) if monitor => normalize_item(&item, true),
+        _ => normalize_body(body),
+    }
+}
+
+// spawns and assignments are signed over the time in nanoseconds when they
+// carry no other data, monitor items are matched by path instead
+fn is_timestamp_data(item: &DataItem) -> bool {
+    let timestamp = !item.data().is_empty() && item.data().iter().all(u8::is_ascii_digit);
+    timestamp && matches!(item.tag("Type"), Some(TYPE_PROCESS | TYPE_ASSIGNMENT))
+}
+
+fn sort_keys(value: Value) -> Value {
+    match value {
+        Value::Object(object) => {
+            let mut entries: Vec<_> = object.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Value::Object(entries.into_iter().map(|(k, v)| (k, sort_keys(v))).collect())
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(sort_keys).collect()),
+        other => other,
+    }
+}
+
+async fn handle(
+    State(shared): State<Arc<Shared>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let path = uri
+        .path_and_query()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_default();
+    let request = RecordedRequest {
+        method: method.to_string(),
+        path,
+        body: normalize_request_body(uri.path(), &body),
+    };
+
+    let response = match shared.mode {
+        CassetteMode::Replay => replay(&shared, &request),
+        CassetteMode::Record => match forward(&shared, method, &request.path, headers, body).await {
+            Ok(response) => {
+                shared.interactions.lock().unwrap().push(Interaction {
+                    request,
+                    response: response.clone(),
+                });
+                shared.used.lock().unwrap().push(true);
+                Ok(response)
+            }
+            Err(e) => Err((502, e.to_string())),
+        },
+    };
+
+    match response {
+        Ok(response) => {
+            let body = if response.base64 {
+                BASE64_STANDARD.decode(&response.body).unwrap_or_default()
+            } else {
+                response.body.into_bytes()
+            };
+            let mut builder = Response::builder().status(response.status);
+            if let Some(content_type) = response.content_type {
+                builder = builder.header(CONTENT_TYPE, content_type);
+            }
+            builder.body(Body::from(body)).unwrap()
+        }
+        Err((status, message)) => Response::builder()
+            .status(status)
+            .body(Body::from(message))
+            .unwrap(),
+    }
+}
+
+fn replay(shared: &Shared, request: &RecordedRequest) -> Result<RecordedResponse, (u16, String)> {
+    let interactions = shared.interactions.lock().unwrap();
+    let mut used = shared.used.lock().unwrap();
+
+    let matching: Vec<usize> = interactions
+        .iter()
+        .enumerate()
+        .filter(|(_, interaction)| interaction.request == *request)
+        .map(|(i, _)| i)
+        .collect();
+    let index = matching
+        .iter()
+        .find(|i| !used[**i])
+        .or(matching.last())
+        .copied()
+        .ok_or_else(|| {
+            (
+                404,
+                format!("no recorded interaction for {} {}", request.method, request.path),
+            )
+        })?;
+
+    used[index] = true;
+    Ok(interactions[index].response.clone())
+}
+
+async fn forward(
+    shared: &Shared,
+    method: Method,
+    path: &str,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<RecordedResponse, Box<dyn std::error::Error + Send + Sync>> {
+    // `/{scheme}/{host}/{path}` back to `{scheme}://{host}/{path}`
+    let (scheme, rest) = path
+        .trim_start_matches('/')
+        .split_once('/')
+        .ok_or("the path does not name a live server")?;
+    let url = format!("{}://{}", scheme, rest);
+
+    let mut request = shared.client.request(method, url).body(body);
+    for name in [CONTENT_TYPE, ACCEPT] {
+        if let Some(value) = headers.get(&name) {
+            request = request.header(name, value);
+        }
+    }
+    let res = request.send().await?;
+
+    let status = res.status().as_u16();
+    let content_type = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let bytes = res.bytes().await?;
+    let (body, base64) = match String::from_utf8(bytes.to_vec()) {
+        Ok(body) => (body, false),
+        Err(_) => (BASE64_STANDARD.encode(&bytes), true),
+    };
+
+    Ok(RecordedResponse {
+        status,
+        content_type,
+        body,
+        base64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ao::Legacy;
+    use crate::cassette::{normalize_body, Cassette, CassetteMode};
+    use crate::data_item::DataItem;
+    use crate::hyperbeam::Hyperbeam;
+    use crate::scheme::{Tag, DEFAULT_MODULE, DEFAULT_SCHEDULER};
+    use crate::testing::MockServer;
+    use crate::utils::random_anchor;
+    use crate::wallet::SignerTypes;
+    use serde_json::{json, Value};
+
+    const PROCESS_ID: &str = "xU9zFkq3X2ZQ6olwNVvr1vUWIjc3kXTWr7xKQD6dh10";
+
+    fn signer() -> SignerTypes {
+        SignerTypes::Arweave("test_key.json".to_string())
+    }
+
+    #[tokio::test]
+    pub async fn test_record_and_replay() {
+        let path = std::env::temp_dir().join(format!(
+            "rusty-ao-cassette-{}.json",
+            random_anchor()
+        ));
+
+        // the mock stands in for the live MU, CU and HyperBEAM node
+        let live = MockServer::start().await.unwrap();
+        let live_url = live.url();
+        live.set_result(PROCESS_ID, "msg", json!({"Output": {"data": "pong"}, "GasUsed": 7}));
+        live.set_process_state(PROCESS_ID, json!({"Balances": {"a": "1"}}));
+
+        let cassette = Cassette::open(&path).await.unwrap();
+        assert_eq!(cassette.mode(), CassetteMode::Record);
+        let ao = Legacy::new(
+            cassette.proxy(&live_url).unwrap(),
+            cassette.proxy(&live_url).unwrap(),
+            signer(),
+        )
+        .unwrap();
+        let hb = Hyperbeam::new(cassette.proxy(&live_url).unwrap(), signer()).unwrap();
+
+        let tags = vec![Tag::new("Action", "Ping")];
+        let sent = ao
+            .send(PROCESS_ID.to_string(), b"ping".to_vec(), tags.clone())
+            .await
+            .unwrap();
+        ao.get(PROCESS_ID.to_string(), "msg".to_string()).await.unwrap();
+        hb.process_now(PROCESS_ID.to_string()).await.unwrap();
+        cassette.save().unwrap();
+        assert_eq!(cassette.interactions().len(), 3);
+        drop(cassette);
+        drop(live);
+
+        // the same calls, with the live server gone
+        let cassette = Cassette::open(&path).await.unwrap();
+        assert_eq!(cassette.mode(), CassetteMode::Replay);
+        let ao = Legacy::new(
+            cassette.proxy(&live_url).unwrap(),
+            cassette.proxy(&live_url).unwrap(),
+            signer(),
+        )
+        .unwrap();
+        let hb = Hyperbeam::new(cassette.proxy(&live_url).unwrap(), signer()).unwrap();
+
+        // a new signature and anchor, but the same normalized body
+        let res = ao
+            .send(PROCESS_ID.to_string(), b"ping".to_vec(), tags)
+            .await
+            .unwrap();
+        assert_eq!(res.id, sent.id);
+        let result = ao.get(PROCESS_ID.to_string(), "msg".to_string()).await.unwrap();
+        assert_eq!(result.output.data, json!("pong"));
+        assert_eq!(result.gas_used, 7);
+        let state = hb.process_now(PROCESS_ID.to_string()).await.unwrap();
+        assert_eq!(state, json!({"Balances": {"a": "1"}}));
+
+        assert!(ao
+            .get(PROCESS_ID.to_string(), "unrecorded".to_string())
+            .await
+            .is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn test_replay_spawn() {
+        let path = std::env::temp_dir().join(format!(
+            "rusty-ao-cassette-{}.json",
+            random_anchor()
+        ));
+        let live = MockServer::start().await.unwrap();
+        let live_url = live.url();
+
+        // spawns, assignments and monitor requests are signed over the time
+        async fn run(cassette: &Cassette, live_url: &str) -> (String, String) {
+            let ao = Legacy::new(
+                cassette.proxy(live_url).unwrap(),
+                cassette.proxy(live_url).unwrap(),
+                signer(),
+            )
+            .unwrap();
+            let spawned = ao
+                .spawn(
+                    "counter".to_string(),
+                    "rusty-ao".to_string(),
+                    DEFAULT_MODULE.to_string(),
+                    DEFAULT_SCHEDULER.to_string(),
+                    vec![],
+                )
+                .await
+                .unwrap();
+            let assignment = ao
+                .assign(PROCESS_ID.to_string(), spawned.id.clone(), vec![])
+                .await
+                .unwrap();
+            ao.monitor(spawned.id.clone()).await.unwrap();
+            (spawned.id, assignment)
+        }
+
+        let cassette = Cassette::record(&path).await.unwrap();
+        let recorded = run(&cassette, &live_url).await;
+        cassette.save().unwrap();
+        drop(cassette);
+        drop(live);
+
+        let cassette = Cassette::replay(&path).await.unwrap();
+        assert_eq!(run(&cassette, &live_url).await, recorded);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn test_replay_fixture() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/testnet.json");
+        let cassette = Cassette::replay(path).await.unwrap();
+        let ao = cassette.legacy(signer());
+        let hb = cassette.hyperbeam(signer());
+
+        let res = ao
+            .get(
+                PROCESS_ID.to_string(),
+                "QQxUa5iAGiCOVLAyVC1vtyZJGKk7ePe3gdP4IhgozQw".to_string(),
+            )
+            .await
+            .unwrap();
+        let notice = res.find_action("Balance-Notice").unwrap();
+        assert_eq!(notice.target, "vh-NTHVvlKZqRxc8LyyTNok65yQ55a_PJ1zWLb9G2JI");
+        assert_eq!(notice.tag("Balance"), Some("1250000000000"));
+        assert_eq!(notice.data, json!("1250000000000"));
+        assert_eq!(res.output.data, json!(""));
+        assert!(res.output.prompt.is_some());
+        assert_eq!(res.gas_used, 0);
+
+        let state = hb.process_now(PROCESS_ID.to_string()).await.unwrap();
+        assert_eq!(state["Ticker"], "AO");
+        assert_eq!(state["Denomination"], 12);
+    }
+
+    #[tokio::test]
+    pub async fn test_proxy_invalid_url() {
+        let cassette = Cassette::replay(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/testnet.json"
+        ))
+        .await
+        .unwrap();
+        assert!(cassette.proxy("not a url").is_err());
+        assert!(cassette.proxy("file:///tmp/x").is_err());
+        assert_eq!(
+            cassette.proxy("https://cu.ao-testnet.xyz/").unwrap(),
+            format!("{}/https/cu.ao-testnet.xyz", cassette.url)
+        );
+    }
+
+    #[test]
+    pub fn test_normalize_body() {
+        assert_eq!(
+            normalize_body(br#"{"b": 1, "a": {"d": 2, "c": 3}}"#),
+            r#"{"a":{"c":3,"d":2},"b":1}"#
+        );
+        assert_eq!(normalize_body(b""), "");
+        assert_eq!(normalize_body(b"plain"), "plain");
+
+        // only the digits of spawns and assignments are timestamps
+        let item = |tags: Vec<Tag>| {
+            let mut item =
+                DataItem::new(1, vec![1; 512], vec![], vec![], tags, b"1700000000".to_vec())
+                    .unwrap();
+            item.set_signature(vec![0; 512]).unwrap();
+            item.to_bytes()
+        };
+        let spawn = normalize_body(&item(vec![Tag::new("Type", "Process")]));
+        assert_eq!(serde_json::from_str::<Value>(&spawn).unwrap()["data"], Value::Null);
+        let untagged = normalize_body(&item(vec![]));
+        assert_eq!(serde_json::from_str::<Value>(&untagged).unwrap()["data"], "1700000000");
+        let message = normalize_body(&item(vec![Tag::new("Type", "Message")]));
+        assert_eq!(serde_json::from_str::<Value>(&message).unwrap()["data"], "1700000000");
+    }
+}
@@ -0,0 +1,335 @@
+use crate::errors::AoErrors;
+use crate::signer::address;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use base64::Engine;
+use bundlr_sdk::consts::{DATAITEM_AS_BUFFER, ONE_AS_BUFFER};
+use bundlr_sdk::deep_hash::DeepHashChunk;
+use bundlr_sdk::deep_hash_sync::deep_hash_sync;
+use bundlr_sdk::index::SignerMap;
+use bundlr_sdk::tags::{AvroDecode, AvroEncode, Tag};
+use sha2::{Digest, Sha256};
+
+/// The id of an AO message: the base64url SHA-256 of its signature
+pub fn message_id(signature: &[u8]) -> String {
+    BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(signature))
+}
+
+/// Signature and owner lengths of the supported ANS-104 signature types
+fn lengths(signature_type: u16) -> Option<(usize, usize)> {
+    match signature_type {
+        1 => Some((512, 512)),
+        2 | 4 => Some((64, 32)),
+        3 => Some((65, 65)),
+        _ => None,
+    }
+}
+
+/// An ANS-104 data item, parsed from its binary form by `from_bytes` or
+/// built and signed by `Legacy`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataItem {
+    signature_type: u16,
+    signature: Vec<u8>,
+    owner: Vec<u8>,
+    target: Vec<u8>,
+    anchor: Vec<u8>,
+    tags: Vec<Tag>,
+    // the tags as serialized in the item, which is what was signed
+    encoded_tags: Vec<u8>,
+    data: Vec<u8>,
+}
+
+impl DataItem {
+    pub(crate) fn new(
+        signature_type: u16,
+        owner: Vec<u8>,
+        target: Vec<u8>,
+        anchor: Vec<u8>,
+        tags: Vec<Tag>,
+        data: Vec<u8>,
+    ) -> Result<Self, AoErrors> {
+        let encoded_tags = if tags.is_empty() {
+            vec![]
+        } else {
+            tags.encode()
+                .map_err(|e| AoErrors::BundlrError {
+                    source: Some(e.into()),
+                })?
+                .to_vec()
+        };
+        Ok(Self {
+            signature_type,
+            signature: vec![],
+            owner,
+            target,
+            anchor,
+            tags,
+            encoded_tags,
+            data,
+        })
+    }
+
+    /// Parses a signed data item. Only the layout is checked, see `verify`
+    /// for the signature.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, AoErrors> {
+        let mut reader = Reader { bytes, offset: 0 };
+
+        let signature_type = u16::from_le_bytes(reader.array()?);
+        let (signature_length, owner_length) = lengths(signature_type).ok_or_else(|| {
+            AoErrors::InvalidDataItem(format!("unsupported signature type {}", signature_type))
+        })?;
+        let signature = reader.take(signature_length)?.to_vec();
+        let owner = reader.take(owner_length)?.to_vec();
+        let target = reader.optional("target")?;
+        let anchor = reader.optional("anchor")?;
+
+        let tag_count = u64::from_le_bytes(reader.array()?);
+        let tags_length = u64::from_le_bytes(reader.array()?);
+        let tags_length = usize::try_from(tags_length)
+            .map_err(|_| AoErrors::InvalidDataItem("tags are too long".to_string()))?;
+        let encoded_tags = reader.take(tags_length)?.to_vec();
+        let tags = if encoded_tags.is_empty() {
+            vec![]
+        } else {
+            encoded_tags
+                .clone()
+                .as_mut_slice()
+                .decode()
+                .map_err(|e| AoErrors::InvalidDataItem(format!("invalid tags: {}", e)))?
+        };
+        if tags.len() as u64 != tag_count {
+            return Err(AoErrors::InvalidDataItem(format!(
+                "{} tags declared, {} encoded",
+                tag_count,
+                tags.len()
+            )));
+        }
+
+        Ok(Self {
+            signature_type,
+            signature,
+            owner,
+            target,
+            anchor,
+            tags,
+            encoded_tags,
+            data: bytes[reader.offset..].to_vec(),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer = vec![];
+        buffer.extend_from_slice(&self.signature_type.to_le_bytes());
+        buffer.extend_from_slice(&self.signature);
+        buffer.extend_from_slice(&self.owner);
+        buffer.push(!self.target.is_empty() as u8);
+        buffer.extend_from_slice(&self.target);
+        buffer.push(!self.anchor.is_empty() as u8);
+        buffer.extend_from_slice(&self.anchor);
+        buffer.extend_from_slice(&(self.tags.len() as u64).to_le_bytes());
+        buffer.extend_from_slice(&(self.encoded_tags.len() as u64).to_le_bytes());
+        buffer.extend_from_slice(&self.encoded_tags);
+        buffer.extend_from_slice(&self.data);
+        buffer
+    }
+
+    /// The ANS-104 deep hash of the item, the message its owner signs
+    pub fn signature_data(&self) -> Result<Vec<u8>, AoErrors> {
+        let message = deep_hash_sync(DeepHashChunk::Chunks(vec![
+            DeepHashChunk::Chunk(DATAITEM_AS_BUFFER.into()),
+            DeepHashChunk::Chunk(ONE_AS_BUFFER.into()),
+            DeepHashChunk::Chunk(self.signature_type.to_string().into_bytes().into()),
+            DeepHashChunk::Chunk(self.owner.clone().into()),
+            DeepHashChunk::Chunk(self.target.clone().into()),
+            DeepHashChunk::Chunk(self.anchor.clone().into()),
+            DeepHashChunk::Chunk(self.encoded_tags.clone().into()),
+            DeepHashChunk::Chunk(self.data.clone().into()),
+        ]))
+        .map_err(|e| AoErrors::BundlrError {
+            source: Some(e.into()),
+        })?;
+        Ok(message.to_vec())
+    }
+
+    pub(crate) fn set_signature(&mut self, signature: Vec<u8>) -> Result<(), AoErrors> {
+        match lengths(self.signature_type) {
+            Some((length, _)) if length == signature.len() => {
+                self.signature = signature;
+                Ok(())
+            }
+            _ => Err(AoErrors::InvalidSigner {
+                source: Some(
+                    format!(
+                        "a {} bytes signature does not match signature type {}",
+                        signature.len(),
+                        self.signature_type
+                    )
+                    .into(),
+                ),
+            }),
+        }
+    }
+
+    /// Checks the signature of the item against its owner
+    pub fn verify(&self) -> Result<(), AoErrors> {
+        if lengths(self.signature_type).is_none() {
+            return Err(AoErrors::InvalidSignature);
+        }
+        SignerMap::from(self.signature_type)
+            .verify(&self.owner, &self.signature_data()?, &self.signature)
+            .map_err(|_| AoErrors::InvalidSignature)
+    }
+
+    /// The message id, see `message_id`
+    pub fn id(&self) -> String {
+        message_id(&self.signature)
+    }
+
+    pub fn signature_type(&self) -> u16 {
+        self.signature_type
+    }
+
+    pub fn signature(&self) -> &[u8] {
+        &self.signature
+    }
+
+    /// The public key of the signer
+    pub fn owner(&self) -> &[u8] {
+        &self.owner
+    }
+
+    /// The native address of the owner, see `signer::address`
+    pub fn owner_address(&self) -> String {
+        address(self.signature_type, &self.owner)
+    }
+
+    /// The base64url id of the target process, if any
+    pub fn target(&self) -> Option<String> {
+        (!self.target.is_empty()).then(|| BASE64_URL_SAFE_NO_PAD.encode(&self.target))
+    }
+
+    pub fn anchor(&self) -> Option<&[u8]> {
+        (!self.anchor.is_empty()).then_some(self.anchor.as_slice())
+    }
+
+    pub fn tags(&self) -> &[Tag] {
+        &self.tags
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|tag| tag.name == name)
+            .map(|tag| tag.value.as_str())
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], AoErrors> {
+        let end = self
+            .offset
+            .checked_add(length)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| AoErrors::InvalidDataItem("unexpected end of data".to_string()))?;
+        let bytes = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], AoErrors> {
+        let mut array = [0u8; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    // a presence byte followed by 32 bytes when set
+    fn optional(&mut self, field: &str) -> Result<Vec<u8>, AoErrors> {
+        match self.array::<1>()?[0] {
+            0 => Ok(vec![]),
+            1 => Ok(self.take(32)?.to_vec()),
+            b => Err(AoErrors::InvalidDataItem(format!(
+                "invalid {} presence byte {}",
+                field, b
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ao::Legacy;
+    use crate::data_item::{message_id, DataItem};
+    use crate::errors::AoErrors;
+    use crate::scheme::Tag;
+    use crate::wallet::{EthereumKey, SignerTypes};
+
+    const PROCESS_ID: &str = "xU9zFkq3X2ZQ6olwNVvr1vUWIjc3kXTWr7xKQD6dh10";
+
+    #[tokio::test]
+    pub async fn test_parse_and_verify() {
+        let signers = [
+            SignerTypes::Arweave("test_key.json".to_string()),
+            SignerTypes::Ethereum(EthereumKey::Hex(
+                "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318".to_string(),
+            )),
+        ];
+        for signer in signers {
+            let ao = Legacy::default_init(signer).unwrap();
+            let message = ao
+                .sign_message(
+                    PROCESS_ID.to_string(),
+                    b"ping".to_vec(),
+                    vec![Tag::new("Action", "Ping")],
+                )
+                .await
+                .unwrap();
+
+            let item = DataItem::from_bytes(&message.bytes).unwrap();
+            assert!(item.verify().is_ok());
+            assert_eq!(item.id(), message.id);
+            assert_eq!(message_id(item.signature()), message.id);
+            assert_eq!(item.owner(), ao.owner().as_slice());
+            assert_eq!(item.owner_address(), ao.address());
+            assert_eq!(item.target().as_deref(), Some(PROCESS_ID));
+            assert_eq!(item.anchor().map(<[u8]>::len), Some(32));
+            assert_eq!(item.tag("Action"), Some("Ping"));
+            assert_eq!(item.data(), b"ping");
+            assert_eq!(item.to_bytes(), message.bytes);
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_reject_forged_item() {
+        let ao = Legacy::default_init(SignerTypes::Arweave("test_key.json".to_string())).unwrap();
+        let message = ao
+            .sign_message(PROCESS_ID.to_string(), b"ping".to_vec(), vec![])
+            .await
+            .unwrap();
+
+        // same signature, different data
+        let mut forged = message.bytes.clone();
+        *forged.last_mut().unwrap() = b'!';
+        let item = DataItem::from_bytes(&forged).unwrap();
+        assert!(matches!(item.verify(), Err(AoErrors::InvalidSignature)));
+
+        for bytes in [
+            &message.bytes[..100],
+            b"not a data item".as_slice(),
+            &[9, 0, 1, 2][..],
+        ] {
+            assert!(matches!(
+                DataItem::from_bytes(bytes),
+                Err(AoErrors::InvalidDataItem(_))
+            ));
+        }
+    }
+}
@@ -1,49 +1,349 @@
-use base64::DecodeError;
-use bundlr_sdk::error::BundlrError;
+use crate::retry::RetryPolicy;
+use crate::scheme::ResponseCu;
+use reqwest::{Response, StatusCode};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use thiserror::Error;
 
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+const BODY_SNIPPET_LEN: usize = 512;
+
+/// A failed request to a MU, CU, SU or HyperBEAM node. `status` is unset
+/// when no response was received, and `body` holds the start of the
+/// response body. The `source` error is not serialized.
+#[derive(Serialize, Deserialize, Error, Debug, Default)]
+pub struct HttpError {
+    pub url: String,
+    pub status: Option<u16>,
+    pub body: Option<String>,
+    #[serde(skip)]
+    #[source]
+    pub source: Option<BoxError>,
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.status {
+            Some(status) => write!(f, "{} from {}", status, self.url)?,
+            None => write!(f, "no response from {}", self.url)?,
+        }
+        match &self.body {
+            Some(body) if !body.is_empty() => write!(f, ": {}", body),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl HttpError {
+    pub(crate) fn request(url: &str, error: reqwest::Error) -> Self {
+        Self {
+            url: error.url().map_or(url, |url| url.as_str()).to_string(),
+            status: error.status().map(|status| status.as_u16()),
+            body: None,
+            source: Some(Box::new(error)),
+        }
+    }
+
+    pub(crate) fn response(
+        url: &str,
+        status: StatusCode,
+        body: &str,
+        source: Option<BoxError>,
+    ) -> Self {
+        let mut end = body.len().min(BODY_SNIPPET_LEN);
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+        Self {
+            url: url.to_string(),
+            status: Some(status.as_u16()),
+            body: Some(body[..end].to_string()),
+            source,
+        }
+    }
+
+    /// Whether the same request may succeed later: connection errors,
+    /// timeouts and 429/502/503/504 responses
+    pub fn is_retryable(&self) -> bool {
+        match self.status {
+            Some(status) => StatusCode::from_u16(status)
+                .map(RetryPolicy::is_retryable_status)
+                .unwrap_or(false),
+            None => self
+                .source
+                .as_ref()
+                .and_then(|source| source.downcast_ref::<reqwest::Error>())
+                .is_some_and(|e| e.is_connect() || e.is_timeout()),
+        }
+    }
+}
+
+/// An error thrown by a process handler, as reported in a CU result
+#[derive(Serialize, Deserialize, Error, Debug, Clone, PartialEq)]
+#[error("{message}")]
+pub struct ProcessError {
+    pub message: String,
+    /// The Lua stack traceback, when the error carries one
+    pub traceback: Option<String>,
+    pub gas_used: i64,
+}
+
+impl ProcessError {
+    pub(crate) fn new(error: String, gas_used: i64) -> Self {
+        let (message, traceback) = match error.split_once("stack traceback:") {
+            Some((message, traceback)) => (message, Some(traceback.trim().to_string())),
+            None => (error.as_str(), None),
+        };
+        Self {
+            message: message.trim().to_string(),
+            traceback,
+            gas_used,
+        }
+    }
+}
+
+/// Reads the body of `res`, failing on a non success status
+pub(crate) async fn response_text(res: Response) -> Result<String, HttpError> {
+    let url = res.url().to_string();
+    let status = res.status();
+    let body = res.text().await.map_err(|e| HttpError {
+        status: Some(status.as_u16()),
+        ..HttpError::request(&url, e)
+    })?;
+
+    if !status.is_success() {
+        return Err(HttpError::response(&url, status, &body, None));
+    }
+    Ok(body)
+}
+
+pub(crate) async fn response_json<T: DeserializeOwned>(res: Response) -> Result<T, AoErrors> {
+    let url = res.url().to_string();
+    let status = res.status();
+    let body = response_text(res)
+        .await
+        .map_err(AoErrors::InvalidServerResponse)?;
+
+    serde_json::from_str(&body).map_err(|e| {
+        AoErrors::InvalidResponseDeserialization(HttpError::response(
+            &url,
+            status,
+            &body,
+            Some(Box::new(e)),
+        ))
+    })
+}
+
+// The message suffix of the variants with an optional source
+fn cause(source: &Option<BoxError>) -> String {
+    source
+        .as_ref()
+        .map(|source| format!(": {}", source))
+        .unwrap_or_default()
+}
+
 #[derive(Serialize, Deserialize, Error, Debug)]
 pub enum AoErrors {
     #[error("Base 64 is not recognized")]
     Base64ReadingError,
 
-    #[error("The instance of bundlr generated an error")]
-    BundlrError,
+    #[error("The instance of bundlr generated an error{}", cause(.source))]
+    BundlrError {
+        #[serde(skip)]
+        #[source]
+        source: Option<BoxError>,
+    },
 
     #[error("Invalid MU API Url")]
     InvalidMUApiUrl,
 
-    #[error("The signer could not be created")]
-    ErrorConstructingSigner,
+    #[error("The signer could not be created{}", cause(.source))]
+    ErrorConstructingSigner {
+        #[serde(skip)]
+        #[source]
+        source: Option<BoxError>,
+    },
+
+    #[error("The HTTP client could not be created{}", cause(.source))]
+    ErrorConstructingClient {
+        #[serde(skip)]
+        #[source]
+        source: Option<BoxError>,
+    },
 
-    #[error("Signer is invalid")]
-    InvalidSigner,
+    #[error("Signer is invalid{}", cause(.source))]
+    InvalidSigner {
+        #[serde(skip)]
+        #[source]
+        source: Option<BoxError>,
+    },
 
     #[error("Transaction is either invalid or broken")]
     InvalidTransaction,
 
-    #[error("The server did not respond as expected")]
-    InvalidServerResponse,
+    #[error("Invalid data item: {0}")]
+    InvalidDataItem(String),
 
-    #[error("The server did not respond as expected")]
-    InvalidResponseDeserialization,
+    #[error("The data item signature does not match its owner")]
+    InvalidSignature,
+
+    #[error("Invalid bundle: {0}")]
+    InvalidBundle(String),
+
+    #[error("The server did not respond as expected: {0}")]
+    InvalidServerResponse(#[source] HttpError),
+
+    #[error("The server response could not be deserialized: {0}")]
+    InvalidResponseDeserialization(#[source] HttpError),
+
+    #[error("Message data could not be deserialized{}", cause(.source))]
+    InvalidMessageData {
+        #[serde(skip)]
+        #[source]
+        source: Option<BoxError>,
+    },
+
+    #[error("Invalid message: {0}")]
+    InvalidMessage(String),
+
+    #[error("Anchor {0} was already used for a different message")]
+    AnchorReused(String),
+
+    /// `last_error` is the last transient failure seen while polling
+    #[error("Timed out waiting for the result of message {message_id}")]
+    ResultTimeout {
+        message_id: String,
+        #[source]
+        last_error: Option<Box<AoErrors>>,
+    },
+
+    #[error("The CU redirected without a valid Location header")]
+    InvalidRedirect,
+
+    #[error("The CU redirected more than {0} times")]
+    TooManyRedirects(usize),
+
+    /// The message was evaluated but its handler threw, `response` is the
+    /// full CU result
+    #[error("The process failed: {error}")]
+    ProcessFailed {
+        error: ProcessError,
+        response: Box<ResponseCu>,
+    },
+}
+
+impl AoErrors {
+    /// Fails with `ProcessFailed` when the result reports a process error
+    pub(crate) fn check_result(response: ResponseCu) -> Result<ResponseCu, AoErrors> {
+        match response.process_error() {
+            Some(error) => Err(AoErrors::ProcessFailed {
+                error,
+                response: Box::new(response),
+            }),
+            None => Ok(response),
+        }
+    }
+
+    /// The HTTP status of a server error, if it got a response
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            AoErrors::InvalidServerResponse(e) | AoErrors::InvalidResponseDeserialization(e) => {
+                e.status
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether retrying the call may succeed, see `HttpError::is_retryable`.
+    /// A result timeout is not, as the message was already sent: fetch its
+    /// result again with `Legacy::get` instead of resending it.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            AoErrors::InvalidServerResponse(e) => e.is_retryable(),
+            _ => false,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Error, Debug)]
 pub enum HbErrors {
-    #[error("Error JSON-parsing the response")]
-    JsonError,
+    #[error("Error JSON-parsing the response: {0}")]
+    JsonError(#[source] HttpError),
 
     #[error("Error retrieving process state")]
     ErrorProcessNow,
 
-    #[error("Invalid hb_node server response")]
-    InvalidServerResponse,
+    #[error("Invalid hb_node server response: {0}")]
+    InvalidServerResponse(#[source] HttpError),
+
+    #[error("The hb_node response could not be deserialized: {0}")]
+    InvalidResponseDeserialization(#[source] HttpError),
 
     #[error("Invalid Hyperbeam node URL")]
     InvalidHbNodeUrl,
 
-    #[error("The signer could not be created")]
-    ErrorConstructingSigner,
+    #[error("The signer could not be created{}", cause(.source))]
+    ErrorConstructingSigner {
+        #[serde(skip)]
+        #[source]
+        source: Option<BoxError>,
+    },
+
+    #[error("The HTTP client could not be created{}", cause(.source))]
+    ErrorConstructingClient {
+        #[serde(skip)]
+        #[source]
+        source: Option<BoxError>,
+    },
+}
+
+impl HbErrors {
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, HbErrors::InvalidServerResponse(e) if e.is_retryable())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::{AoErrors, HttpError};
+    use reqwest::StatusCode;
+    use std::error::Error;
+
+    #[test]
+    pub fn test_http_error() {
+        let body = "x".repeat(1000);
+        let error = HttpError::response(
+            "https://cu.ao-testnet.xyz/result/msg",
+            StatusCode::SERVICE_UNAVAILABLE,
+            &body,
+            None,
+        );
+        assert_eq!(error.status, Some(503));
+        assert_eq!(error.body.as_ref().unwrap().len(), 512);
+        assert!(error.is_retryable());
+
+        let source = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        let error = AoErrors::InvalidResponseDeserialization(HttpError::response(
+            "https://cu.ao-testnet.xyz/result/msg",
+            StatusCode::OK,
+            "{",
+            Some(Box::new(source)),
+        ));
+        assert!(!error.is_retryable());
+        assert!(error.to_string().contains("200 from https://cu.ao-testnet.xyz/result/msg: {"));
+        assert!(error.source().unwrap().source().is_some());
+
+        // the source error is dropped on serialization
+        let json = serde_json::to_string(&error).unwrap();
+        let error: AoErrors = serde_json::from_str(&json).unwrap();
+        match error {
+            AoErrors::InvalidResponseDeserialization(e) => {
+                assert_eq!(e.status, Some(200));
+                assert!(e.source.is_none());
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
 }
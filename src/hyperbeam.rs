@@ -1,63 +1,169 @@
+use crate::retry::RetryPolicy;
 use crate::scheme::HB_NODE_ENDPOINT;
+use crate::signer::{AoSigner, SignerSource};
 use crate::{
-    errors::HbErrors,
-    wallet::{SignerTypes, Signers},
+    errors::{response_text, HbErrors, HttpError},
+    wallet::SignerTypes,
 };
-use bundlr_sdk::currency::arweave::ArweaveBuilder;
-use bundlr_sdk::currency::solana::{Solana, SolanaBuilder};
-use std::path::PathBuf;
+use crate::utils::HttpConfig;
+use reqwest::header::HeaderMap;
+use reqwest::{Client, StatusCode};
+use std::sync::Arc;
+use std::time::Duration;
 use serde_json::Value;
 
+/// Configures a `Hyperbeam` client, see `LegacyBuilder`
+pub struct HyperbeamBuilder {
+    node_endpoint: String,
+    signer: SignerSource,
+    client: Option<Client>,
+    http: HttpConfig,
+    retry: RetryPolicy,
+}
+
+impl HyperbeamBuilder {
+    pub fn new(signer: SignerTypes) -> Self {
+        Self::from_source(SignerSource::Wallet(signer))
+    }
+
+    pub fn with_signer(signer: Arc<dyn AoSigner>) -> Self {
+        Self::from_source(SignerSource::Custom(signer))
+    }
+
+    fn from_source(signer: SignerSource) -> Self {
+        Self {
+            node_endpoint: HB_NODE_ENDPOINT.to_string(),
+            signer,
+            client: None,
+            http: HttpConfig::default(),
+            retry: RetryPolicy::none(),
+        }
+    }
+
+    pub fn node_endpoint(mut self, node_endpoint: String) -> Self {
+        self.node_endpoint = node_endpoint;
+        self
+    }
+
+    pub fn client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.http.timeout = Some(timeout);
+        self
+    }
+
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.http.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// Retries of the node requests, none by default
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: String) -> Self {
+        self.http.user_agent = user_agent;
+        self
+    }
+
+    pub fn default_headers(mut self, headers: HeaderMap) -> Self {
+        self.http.default_headers = headers;
+        self
+    }
+
+    pub fn build(self) -> Result<Hyperbeam, HbErrors> {
+        let client = match self.client {
+            Some(client) => client,
+            None => self
+                .http
+                .build_client()
+                .map_err(|e| HbErrors::ErrorConstructingClient {
+                    source: Some(e.into()),
+                })?,
+        };
+
+        let signer_type = match &self.signer {
+            SignerSource::Wallet(wallet) => Some(wallet.clone()),
+            SignerSource::Custom(_) => None,
+        };
+        Ok(Hyperbeam {
+            node_endpoint: self.node_endpoint,
+            signer_type,
+            signer: self
+                .signer
+                .build()
+                .map_err(|e| HbErrors::ErrorConstructingSigner { source: Some(e) })?,
+            client,
+            retry: self.retry,
+        })
+    }
+}
+
 pub struct Hyperbeam {
     pub node_endpoint: String,
-    pub signer_type: SignerTypes,
-    pub signer: Signers,
+    /// The wallet the client was built from, `None` for a custom signer
+    pub signer_type: Option<SignerTypes>,
+    pub signer: Arc<dyn AoSigner>,
+    pub client: Client,
+    pub retry: RetryPolicy,
 }
 
 impl Hyperbeam {
     pub fn new(node_endpoint: String, signer: SignerTypes) -> Result<Self, HbErrors> {
-        Ok(Self {
-            node_endpoint: node_endpoint,
-            signer_type: signer.clone(),
-            signer: Self::signer(&signer)?,
-        })
+        HyperbeamBuilder::new(signer)
+            .node_endpoint(node_endpoint)
+            .build()
     }
 
     pub fn default_init(signer: SignerTypes) -> Result<Self, HbErrors> {
-        Ok(Self {
-            node_endpoint: HB_NODE_ENDPOINT.to_string(),
-            signer_type: signer.clone(),
-            signer: Self::signer(&signer)?,
-        })
+        HyperbeamBuilder::new(signer).build()
     }
 
-    fn signer(signer: &SignerTypes) -> Result<Signers, HbErrors> {
-        match signer {
-            SignerTypes::Solana(p) => Ok(Signers::Solana(
-                SolanaBuilder::new()
-                    .wallet(p)
-                    .build()
-                    .map_err(|_| HbErrors::ErrorConstructingSigner)?,
-            )),
-            SignerTypes::Arweave(keypair_path) => Ok(Signers::Arweave(
-                ArweaveBuilder::new()
-                    .keypair_path(PathBuf::from(keypair_path.clone()))
-                    .build()
-                    .map_err(|_| HbErrors::ErrorConstructingSigner)?,
-            )),
-        }
+    pub fn builder(signer: SignerTypes) -> HyperbeamBuilder {
+        HyperbeamBuilder::new(signer)
     }
 
-    pub async fn process_now(self, process_id: String) -> Result<Value, HbErrors> {
-        let dev_process_url = format!("{}/{}~process@1.0/now", self.node_endpoint, process_id);
-        let state = reqwest::Client::new()
-            .get(dev_process_url)
-            .send()
+    /// The public key of the wallet, see `AoSigner::owner`
+    pub fn owner(&self) -> Vec<u8> {
+        self.signer.owner()
+    }
+
+    pub fn address(&self) -> String {
+        self.signer.address()
+    }
+
+    async fn get_text(&self, url: String) -> Result<String, HbErrors> {
+        let res = self
+            .retry
+            .send(|| self.client.get(&url))
             .await
-            .map_err(|_| HbErrors::InvalidServerResponse)?
-            .text()
+            .map_err(|e| HbErrors::InvalidServerResponse(HttpError::request(&url, e)))?;
+
+        response_text(res)
             .await
-            .map_err(|_| HbErrors::InvalidServerResponse)?;
+            .map_err(HbErrors::InvalidServerResponse)
+    }
+
+    async fn get_json(&self, url: String) -> Result<Value, HbErrors> {
+        let body = self.get_text(url.clone()).await?;
+        serde_json::from_str(&body).map_err(|e| {
+            HbErrors::InvalidResponseDeserialization(HttpError::response(
+                &url,
+                StatusCode::OK,
+                &body,
+                Some(Box::new(e)),
+            ))
+        })
+    }
+
+    pub async fn process_now(&self, process_id: String) -> Result<Value, HbErrors> {
+        let dev_process_url = format!("{}/{}~process@1.0/now", self.node_endpoint, process_id);
+        let state = self.get_text(dev_process_url.clone()).await?;
 
         // target section header
         let target_header = "content-disposition: form-data;name=\"overview/data\"";
@@ -72,7 +178,14 @@ impl Hyperbeam {
 
                     match serde_json::from_str::<serde_json::Value>(data) {
                         Ok(state) => return Ok(state),
-                        Err(e) => return Err(HbErrors::JsonError),
+                        Err(e) => {
+                            return Err(HbErrors::JsonError(HttpError::response(
+                                &dev_process_url,
+                                StatusCode::OK,
+                                data,
+                                Some(Box::new(e)),
+                            )))
+                        }
                     }
                 }
             }
@@ -81,117 +194,140 @@ impl Hyperbeam {
         Err(HbErrors::ErrorProcessNow)
     }
 
-    pub async fn meta_info(self) -> Result<Value, HbErrors> {
-        let client = reqwest::Client::new();
-        
+    pub async fn meta_info(&self) -> Result<Value, HbErrors> {
         let req_url = format!("{}/~meta@1.0/info/serialize~json@1.0", self.node_endpoint);
-        let response = client.get(req_url)
-            .send()
-            .await.map_err(|_| HbErrors::InvalidServerResponse)?;
-        
-        if !response.status().is_success() {
-            return Err(HbErrors::InvalidServerResponse);
-        }
-        
-        let body = response.json().await.map_err(|_| HbErrors::InvalidServerResponse)?;
-        
-        Ok(body)
+        self.get_json(req_url).await
     }
 
-    pub async fn meta_info_address(self) -> Result<String, HbErrors> {
-        let client = reqwest::Client::new();
-        
+    pub async fn meta_info_address(&self) -> Result<String, HbErrors> {
         let req_url = format!("{}/~meta@1.0/info/address", self.node_endpoint);
-        let response = client.get(req_url)
-            .send()
-            .await.map_err(|_| HbErrors::InvalidServerResponse)?;
-        
-        if !response.status().is_success() {
-            return Err(HbErrors::InvalidServerResponse);
-        }
-        
-        let body = response.text().await.map_err(|_| HbErrors::InvalidServerResponse)?;
-        
-        Ok(body)
+        self.get_text(req_url).await
     }
 
-    pub async fn router_routes(self) -> Result<Value, HbErrors> {
-        let client = reqwest::Client::new();
-        
+    pub async fn router_routes(&self) -> Result<Value, HbErrors> {
         let req_url = format!("{}/~router@1.0/routes/serialize~json@1.0", self.node_endpoint);
-        let response = client.get(req_url)
-            .send()
-            .await.map_err(|_| HbErrors::InvalidServerResponse)?;
-        
-        if !response.status().is_success() {
-            return Err(HbErrors::InvalidServerResponse);
-        }
-        
-        let body = response.json().await.map_err(|_| HbErrors::InvalidServerResponse)?;
-        
-        Ok(body)
-    } 
-
-    pub async fn hyperbuddy_metrics(self) -> Result<String, HbErrors> {
-        let client = reqwest::Client::new();
-        
+        self.get_json(req_url).await
+    }
+
+    pub async fn hyperbuddy_metrics(&self) -> Result<String, HbErrors> {
         let req_url = format!("{}/~hyperbuddy@1.0/index/~hyperbuddy@1.0/metrics", self.node_endpoint);
-        let response = client.get(req_url)
-            .send()
-            .await.map_err(|_| HbErrors::InvalidServerResponse)?;
-        
-        if !response.status().is_success() {
-            return Err(HbErrors::InvalidServerResponse);
-        }
-        
-        let body = response.text().await.map_err(|_| HbErrors::InvalidServerResponse)?;
-        
-        Ok(body)
-    }  
+        self.get_text(req_url).await
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::errors::HbErrors;
     use crate::hyperbeam::Hyperbeam;
+    use crate::retry::RetryPolicy;
     use crate::scheme::HB_NODE_ENDPOINT;
+    use crate::testing::{MockResponse, MockServer};
+    use reqwest::Method;
     use crate::wallet::SignerTypes;
+    use std::time::Duration;
+
     #[tokio::test]
     pub async fn test_init() {
-        let hb = Hyperbeam::new(
+        let _hb = Hyperbeam::new(
             HB_NODE_ENDPOINT.to_string(),
             SignerTypes::Arweave("test_key.json".to_string()),
         )
         .unwrap();
     }
 
+    #[tokio::test]
+    pub async fn test_builder() {
+        let hb = Hyperbeam::builder(SignerTypes::Arweave("test_key.json".to_string()))
+            .node_endpoint(HB_NODE_ENDPOINT.to_string())
+            .timeout(Duration::from_secs(30))
+            .user_agent("indexer/1.0".to_string())
+            .retry_policy(RetryPolicy::default())
+            .build()
+            .unwrap();
+        assert_eq!(hb.node_endpoint, HB_NODE_ENDPOINT);
+        assert_eq!(hb.retry.max_attempts, RetryPolicy::default().max_attempts);
+        assert!(matches!(hb.signer_type, Some(SignerTypes::Arweave(_))));
+
+        // the settings reach the client, and the getters borrow it
+        let mock = MockServer::start().await.unwrap();
+        let hb = Hyperbeam::builder(SignerTypes::Arweave("test_key.json".to_string()))
+            .node_endpoint(mock.url())
+            .timeout(Duration::from_millis(200))
+            .user_agent("indexer/1.0".to_string())
+            .build()
+            .unwrap();
+        hb.meta_info().await.unwrap();
+        hb.router_routes().await.unwrap();
+        assert_eq!(mock.requests()[0].header("user-agent"), Some("indexer/1.0"));
+
+        mock.respond(
+            Method::GET,
+            "/~meta@1.0/info/address",
+            MockResponse::text("late").delay(Duration::from_secs(5)),
+        );
+        match hb.meta_info_address().await {
+            Err(HbErrors::InvalidServerResponse(e)) => assert!(e.status.is_none()),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
     #[tokio::test]
     pub async fn test_default_init() {
-        let hb =
+        let _hb =
             Hyperbeam::default_init(SignerTypes::Arweave("test_key.json".to_string())).unwrap();
     }
 
     #[tokio::test]
     pub async fn test_process_now() {
-        let hb =
-            Hyperbeam::default_init(SignerTypes::Arweave("test_key.json".to_string())).unwrap();
+        let mock = MockServer::start().await.unwrap();
+        mock.set_process_state(
+            "oQZQd1-MztVOxODecwrxFR9UGUnsrX5wGseMJ9iSH38",
+            serde_json::json!({"Name": "mock"}),
+        );
+        let hb = mock.hyperbeam(SignerTypes::Arweave("test_key.json".to_string()));
         let state = hb
             .process_now("oQZQd1-MztVOxODecwrxFR9UGUnsrX5wGseMJ9iSH38".to_string())
             .await
             .unwrap();
         println!("{:?}", state);
+        assert_eq!(state["Name"], "mock");
+    }
+
+    #[tokio::test]
+    pub async fn test_process_now_invalid_json() {
+        let mock = MockServer::start().await.unwrap();
+        let process_id = "oQZQd1-MztVOxODecwrxFR9UGUnsrX5wGseMJ9iSH38";
+        mock.respond(
+            Method::GET,
+            &format!("/{}~process@1.0/now", process_id),
+            MockResponse::text(
+                "--mock\r\ncontent-disposition: form-data;name=\"overview/data\"\r\n\r\n{\"Name\":\r\n--mock--\r\n",
+            ),
+        );
+        let hb = mock.hyperbeam(SignerTypes::Arweave("test_key.json".to_string()));
+        match hb.process_now(process_id.to_string()).await {
+            Err(HbErrors::JsonError(e)) => {
+                assert_eq!(e.body.as_deref(), Some("{\"Name\":"));
+                assert!(e.url.ends_with("~process@1.0/now"));
+                assert!(e.source.is_some());
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
     }
 
     #[tokio::test]
     pub async fn test_meta_info() {
-        let hb = Hyperbeam::default_init(SignerTypes::Arweave("test_key.json".to_string())).unwrap();
+        let mock = MockServer::start().await.unwrap();
+        let hb = mock.hyperbeam(SignerTypes::Arweave("test_key.json".to_string()));
         let node_info = hb.meta_info().await.unwrap();
         println!("{:?}", node_info);
-        assert!(node_info.to_string().len() > 0);
+        assert!(!node_info.to_string().is_empty());
     }
 
     #[tokio::test]
     pub async fn test_meta_info_address() {
-        let hb = Hyperbeam::default_init(SignerTypes::Arweave("test_key.json".to_string())).unwrap();
+        let mock = MockServer::start().await.unwrap();
+        let hb = mock.hyperbeam(SignerTypes::Arweave("test_key.json".to_string()));
         let node_address = hb.meta_info_address().await.unwrap();
         println!("{:?}", node_address);
         assert!(node_address.len() == 43);
@@ -199,17 +335,19 @@ mod tests {
 
     #[tokio::test]
     pub async fn test_router_routes() {
-        let hb = Hyperbeam::default_init(SignerTypes::Arweave("test_key.json".to_string())).unwrap();
+        let mock = MockServer::start().await.unwrap();
+        let hb = mock.hyperbeam(SignerTypes::Arweave("test_key.json".to_string()));
         let node_routes = hb.router_routes().await.unwrap();
         println!("{:?}", node_routes);
-        assert!(node_routes.to_string().len() > 0);
+        assert!(!node_routes.to_string().is_empty());
     }
 
     #[tokio::test]
     pub async fn test_hyperbuddy_metrics() {
-        let hb = Hyperbeam::default_init(SignerTypes::Arweave("test_key.json".to_string())).unwrap();
+        let mock = MockServer::start().await.unwrap();
+        let hb = mock.hyperbeam(SignerTypes::Arweave("test_key.json".to_string()));
         let node_metrics = hb.hyperbuddy_metrics().await.unwrap();
         println!("{:?}", node_metrics);
-        assert!(node_metrics.to_string().len() > 0);
+        assert!(!node_metrics.to_string().is_empty());
     }
 }
@@ -1,7 +1,20 @@
 pub mod ao;
+pub mod bundle;
+#[cfg(feature = "testing")]
+pub mod cassette;
+pub mod data_item;
 pub mod errors;
 pub mod hyperbeam;
+pub mod message;
 pub mod scheme;
 pub mod wallet;
 pub mod utils;
+pub mod process;
+pub mod retry;
+#[cfg(feature = "server")]
 pub mod server;
+pub mod signer;
+pub mod su;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod trace;
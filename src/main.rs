@@ -1,6 +1,6 @@
 pub mod utils;
 
-use rusty_ao::server::{status_handler, node_info_handler, node_routes_handler, node_metrics_handler};
+use rusty_ao::server::{AppState, status_handler, node_info_handler, node_routes_handler, node_metrics_handler};
 use shuttle_axum::axum::{
     Router,
     http::{Method, header},
@@ -20,6 +20,9 @@ async fn main(
         });
     }
 
+    // fails the deployment on a missing or invalid AO_WALLET, with its cause
+    let state = AppState::from_env().map_err(shuttle_runtime::CustomError::new)?;
+
     let timeout_layer = TimeoutLayer::new(Duration::from_secs(3600));
     let cors_layer = CorsLayer::new()
         .allow_origin(tower_http::cors::Any)
@@ -32,6 +35,7 @@ async fn main(
         .route("/node/{address}/routes", get(node_routes_handler))
         .route("/node/{address}", get(node_metrics_handler))
         .layer(timeout_layer)
-        .layer(cors_layer);
+        .layer(cors_layer)
+        .with_state(state);
     Ok(router.into())
 }
\ No newline at end of file
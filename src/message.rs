@@ -0,0 +1,231 @@
+use crate::ao::{Legacy, WaitConfig};
+use crate::errors::AoErrors;
+use crate::scheme::{ResponseCu, ResponseMu, Tag, TYPE_MESSAGE};
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use base64::Engine;
+use bundlr_sdk::BundlrTx;
+use serde::Serialize;
+
+// ANS-104 limits
+const MAX_TAGS: usize = 128;
+const MAX_TAG_NAME_BYTES: usize = 1024;
+const MAX_TAG_VALUE_BYTES: usize = 3072;
+pub(crate) const ANCHOR_BYTES: usize = 32;
+pub(crate) const TARGET_BYTES: usize = 32;
+
+const PROTOCOL_TAGS: [&str; 4] = ["Data-Protocol", "Variant", "Type", "SDK"];
+
+/// Builds, validates and signs a message to a process. The protocol tags
+/// from `Legacy::get_base_tags` are always prepended.
+pub struct MessageBuilder<'a> {
+    ao: &'a Legacy,
+    target: String,
+    anchor: Option<String>,
+    tags: Vec<Tag>,
+    data: Vec<u8>,
+    error: Option<AoErrors>,
+}
+
+impl<'a> MessageBuilder<'a> {
+    pub fn new(ao: &'a Legacy, target: String) -> Self {
+        Self {
+            ao,
+            target,
+            anchor: None,
+            tags: vec![],
+            data: vec![],
+            error: None,
+        }
+    }
+
+    pub fn action(self, action: &str) -> Self {
+        self.tag("Action", action)
+    }
+
+    pub fn tag(mut self, name: &str, value: &str) -> Self {
+        self.tags.push(Tag::new(name, value));
+        self
+    }
+
+    pub fn tags(mut self, tags: Vec<Tag>) -> Self {
+        self.tags.extend(tags);
+        self
+    }
+
+    pub fn data_bytes(mut self, data: Vec<u8>) -> Self {
+        self.data = data;
+        self
+    }
+
+    /// Serializes `data` as JSON, defaulting the content type to
+    /// `application/json`.
+    pub fn data_json<T: Serialize>(mut self, data: &T) -> Self {
+        match serde_json::to_vec(data) {
+            Ok(data) => self.data = data,
+            Err(e) => self.error = Some(AoErrors::InvalidMessage(e.to_string())),
+        }
+        if self.content_type_tag().is_none() {
+            self = self.content_type("application/json");
+        }
+        self
+    }
+
+    /// Sets the ANS-104 anchor, which must be exactly 32 bytes. A random
+    /// anchor is generated when none is set.
+    pub fn anchor(mut self, anchor: &str) -> Self {
+        self.anchor = Some(anchor.to_string());
+        self
+    }
+
+    pub fn content_type(mut self, content_type: &str) -> Self {
+        self.tags.retain(|tag| tag.name != "Content-Type");
+        self.tag("Content-Type", content_type)
+    }
+
+    fn content_type_tag(&self) -> Option<&Tag> {
+        self.tags.iter().find(|tag| tag.name == "Content-Type")
+    }
+
+    fn validate(&self) -> Result<(), AoErrors> {
+        let invalid = |reason: String| Err(AoErrors::InvalidMessage(reason));
+
+        match BASE64_URL_SAFE_NO_PAD.decode(&self.target) {
+            Ok(target) if target.len() == TARGET_BYTES => {}
+            _ => return invalid(format!("target {} is not a valid id", self.target)),
+        }
+        if let Some(anchor) = &self.anchor {
+            check_anchor(anchor.as_bytes())?;
+        }
+        if self.tags.len() + PROTOCOL_TAGS.len() > MAX_TAGS {
+            return invalid(format!("more than {} tags", MAX_TAGS));
+        }
+        for tag in &self.tags {
+            if tag.name.is_empty() || tag.name.len() > MAX_TAG_NAME_BYTES {
+                return invalid(format!("invalid tag name {:?}", tag.name));
+            }
+            if tag.value.is_empty() || tag.value.len() > MAX_TAG_VALUE_BYTES {
+                return invalid(format!("invalid value for tag {}", tag.name));
+            }
+            if PROTOCOL_TAGS.contains(&tag.name.as_str()) {
+                return invalid(format!("tag {} is set by the protocol", tag.name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Validates the message and returns it as a signed data item
+    pub async fn build(mut self) -> Result<BundlrTx, AoErrors> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        self.validate()?;
+
+        let mut tags = Legacy::get_base_tags(TYPE_MESSAGE.to_string());
+        tags.extend(self.tags);
+        self.ao
+            .sign_bundle_item(self.target, self.anchor.as_deref(), self.data, tags)
+            .await
+    }
+
+    pub async fn send(self) -> Result<ResponseMu, AoErrors> {
+        let ao = self.ao;
+        let payload = self
+            .build()
+            .await?
+            .as_bytes()
+            .map_err(|_| AoErrors::InvalidTransaction)?;
+        ao.post_to_mu(payload).await
+    }
+
+    /// Sends the message through `Legacy::send_idempotent`, keyed by the
+    /// anchor, which must be set.
+    pub async fn send_idempotent(self) -> Result<ResponseMu, AoErrors> {
+        let ao = self.ao;
+        let Some(anchor) = self.anchor.clone() else {
+            return Err(AoErrors::InvalidMessage(
+                "idempotent sends need an anchor".to_string(),
+            ));
+        };
+        let content = Legacy::content_hash(&self.target, &self.data, &self.tags);
+        let payload = ao.idempotent_item(&anchor, content, || self.build()).await?;
+        ao.post_to_mu(payload).await
+    }
+
+    pub async fn send_and_wait(self, wait: WaitConfig) -> Result<ResponseCu, AoErrors> {
+        let ao = self.ao;
+        let process_id = self.target.clone();
+        let res = self.send().await?;
+        ao.wait_for_result(process_id, res.id, wait).await
+    }
+}
+
+pub(crate) fn check_anchor(anchor: &[u8]) -> Result<(), AoErrors> {
+    if anchor.len() != ANCHOR_BYTES {
+        return Err(AoErrors::InvalidMessage(format!(
+            "anchor must be {} bytes",
+            ANCHOR_BYTES
+        )));
+    }
+    Ok(())
+}
+
+impl Legacy {
+    pub fn message(&self, process_id: String) -> MessageBuilder<'_> {
+        MessageBuilder::new(self, process_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ao::Legacy;
+    use crate::errors::AoErrors;
+    use crate::wallet::SignerTypes;
+    use serde_json::json;
+
+    const PROCESS_ID: &str = "xU9zFkq3X2ZQ6olwNVvr1vUWIjc3kXTWr7xKQD6dh10";
+
+    #[tokio::test]
+    pub async fn test_build_signed_message() {
+        let ao = Legacy::default_init(SignerTypes::Arweave("test_key.json".to_string())).unwrap();
+        let anchor = "00000000000000000000000000000042";
+        let mut tx = ao
+            .message(PROCESS_ID.to_string())
+            .action("Transfer")
+            .tag("Recipient", "vh-NTHVvlKZqRxc8LyyTNok65yQ55a_PJ1zWLb9G2JI")
+            .data_json(&json!({ "memo": "hello" }))
+            .anchor(anchor)
+            .build()
+            .await
+            .unwrap();
+
+        assert!(tx.is_signed());
+        assert!(tx.verify().await.is_ok());
+
+        // signature type, signature and owner precede the target and anchor
+        let bytes = tx.as_bytes().unwrap();
+        let anchor_start = 2 + 512 + 512 + 1 + 32 + 1;
+        assert_eq!(&bytes[anchor_start..anchor_start + 32], anchor.as_bytes());
+    }
+
+    #[tokio::test]
+    pub async fn test_build_invalid_message() {
+        let ao = Legacy::default_init(SignerTypes::Arweave("test_key.json".to_string())).unwrap();
+
+        let res = ao.message("not-an-id".to_string()).build().await;
+        assert!(matches!(res, Err(AoErrors::InvalidMessage(_))));
+
+        let res = ao
+            .message(PROCESS_ID.to_string())
+            .anchor("too-short")
+            .build()
+            .await;
+        assert!(matches!(res, Err(AoErrors::InvalidMessage(_))));
+
+        let res = ao
+            .message(PROCESS_ID.to_string())
+            .tag("Type", "Process")
+            .build()
+            .await;
+        assert!(matches!(res, Err(AoErrors::InvalidMessage(_))));
+    }
+}
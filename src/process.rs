@@ -0,0 +1,54 @@
+use crate::ao::{Legacy, WaitConfig};
+use crate::errors::AoErrors;
+use crate::scheme::{ResponseCu, ResponseMu, Tag};
+
+/// A handle bound to a process id, forwarding calls to its `Legacy` client
+pub struct Process<'a> {
+    ao: &'a Legacy,
+    id: String,
+}
+
+impl<'a> Process<'a> {
+    pub fn new(ao: &'a Legacy, id: String) -> Self {
+        Self { ao, id }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub async fn send(&self, data: Vec<u8>, tags: Vec<Tag>) -> Result<ResponseMu, AoErrors> {
+        self.ao.send(self.id.clone(), data, tags).await
+    }
+
+    pub async fn send_and_wait(
+        &self,
+        data: Vec<u8>,
+        tags: Vec<Tag>,
+        wait: WaitConfig,
+    ) -> Result<ResponseCu, AoErrors> {
+        self.ao
+            .send_and_wait(self.id.clone(), data, tags, wait)
+            .await
+    }
+
+    pub async fn eval(&self, code: String) -> Result<ResponseMu, AoErrors> {
+        self.ao.eval(self.id.clone(), code).await
+    }
+
+    pub async fn eval_and_wait(
+        &self,
+        code: String,
+        wait: WaitConfig,
+    ) -> Result<ResponseCu, AoErrors> {
+        self.ao.eval_and_wait(self.id.clone(), code, wait).await
+    }
+
+    pub async fn dry_run(&self, data: String, tags: Vec<Tag>) -> Result<ResponseCu, AoErrors> {
+        self.ao.dry_run(self.id.clone(), data, tags).await
+    }
+
+    pub async fn result(&self, message_id: String) -> Result<ResponseCu, AoErrors> {
+        self.ao.get(self.id.clone(), message_id).await
+    }
+}
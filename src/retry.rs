@@ -0,0 +1,194 @@
+use rand::Rng;
+use reqwest::header::RETRY_AFTER;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::time::{Duration, SystemTime};
+
+/// Retries of transient MU, CU and HyperBEAM failures: connection errors,
+/// timeouts and 429/502/503/504 responses. Delays grow exponentially with
+/// jitter, unless the server sets a `Retry-After` (in seconds or as an HTTP
+/// date), which is capped at `max_backoff`.
+///
+/// Message submissions are signed once and the same data item is posted on
+/// every attempt, so a retry can never produce a second message id.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub multiplier: f64,
+    /// Randomizes each delay between half and all of the computed backoff
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(10),
+            multiplier: 2.0,
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A single attempt, the default of `Legacy` and `Hyperbeam`
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_retryable_status(status: StatusCode) -> bool {
+        matches!(
+            status,
+            StatusCode::TOO_MANY_REQUESTS
+                | StatusCode::BAD_GATEWAY
+                | StatusCode::SERVICE_UNAVAILABLE
+                | StatusCode::GATEWAY_TIMEOUT
+        )
+    }
+
+    /// Delay before the attempt following `attempt` (starting at 1)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        // computed in f64 so large attempts saturate instead of overflowing
+        let secs = self.initial_backoff.as_secs_f64()
+            * self.multiplier.powf(f64::from(attempt.saturating_sub(1)));
+        let backoff = Duration::try_from_secs_f64(secs)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+        if self.jitter {
+            backoff.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+        } else {
+            backoff
+        }
+    }
+
+    /// Sends the request built by `request` until it succeeds, fails with a
+    /// non transient error or the attempts are exhausted. The last response
+    /// (or error) is returned as is.
+    pub async fn send<F>(&self, request: F) -> Result<Response, reqwest::Error>
+    where
+        F: Fn() -> RequestBuilder,
+    {
+        let mut attempt = 1;
+        loop {
+            let res = request().send().await;
+            let (retryable, retry_after) = match &res {
+                Ok(res) => (Self::is_retryable_status(res.status()), retry_after(res)),
+                Err(e) => (e.is_connect() || e.is_timeout(), None),
+            };
+            if !retryable || attempt >= self.max_attempts {
+                return res;
+            }
+
+            let delay = match retry_after {
+                Some(retry_after) => retry_after.min(self.max_backoff),
+                None => self.backoff(attempt),
+            };
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+fn retry_after(res: &Response) -> Option<Duration> {
+    let value = res.headers().get(RETRY_AFTER)?.to_str().ok()?;
+    parse_retry_after(value, SystemTime::now())
+}
+
+// delay-seconds or an HTTP date, a date in the past means no delay
+fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(now).unwrap_or(Duration::ZERO))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::retry::{parse_retry_after, RetryPolicy};
+    use crate::testing::{MockResponse, MockServer};
+    use reqwest::Method;
+    use std::time::{Duration, Instant, UNIX_EPOCH};
+
+    #[test]
+    pub fn test_backoff() {
+        let policy = RetryPolicy {
+            jitter: false,
+            ..Default::default()
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(250));
+        assert_eq!(policy.backoff(3), Duration::from_secs(1));
+        assert_eq!(policy.backoff(10), policy.max_backoff);
+        assert_eq!(policy.backoff(u32::MAX), policy.max_backoff);
+        assert_eq!(policy.backoff(0), Duration::from_millis(250));
+
+        let policy = RetryPolicy::default();
+        let backoff = policy.backoff(2);
+        assert!(backoff >= Duration::from_millis(250) && backoff <= Duration::from_millis(500));
+    }
+
+    #[test]
+    pub fn test_parse_retry_after() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_445_412_480);
+        assert_eq!(parse_retry_after(" 120 ", now), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:27:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[tokio::test]
+    pub async fn test_retry_transient_status() {
+        let mock = MockServer::start().await.unwrap();
+        mock.respond(
+            Method::GET,
+            "/ping",
+            MockResponse::new(503).header("retry-after", "0"),
+        );
+        mock.respond(Method::GET, "/ping", MockResponse::text("ok"));
+
+        let client = reqwest::Client::new();
+        let url = format!("{}/ping", mock.url());
+        let res = RetryPolicy::default()
+            .send(|| client.get(&url))
+            .await
+            .unwrap();
+        assert!(res.status().is_success());
+        assert_eq!(res.text().await.unwrap(), "ok");
+        assert_eq!(mock.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    pub async fn test_retry_after_capped() {
+        let mock = MockServer::start().await.unwrap();
+        mock.respond(
+            Method::GET,
+            "/ping",
+            MockResponse::new(429).header("retry-after", "3600"),
+        );
+        mock.respond(Method::GET, "/ping", MockResponse::text("ok"));
+
+        let client = reqwest::Client::new();
+        let url = format!("{}/ping", mock.url());
+        let policy = RetryPolicy {
+            max_backoff: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let res = policy.send(|| client.get(&url)).await.unwrap();
+        assert!(res.status().is_success());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
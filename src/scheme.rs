@@ -1,3 +1,7 @@
+use crate::errors::{AoErrors, ProcessError};
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
 // Constants Legacy
@@ -5,9 +9,13 @@ pub const DATA_PROTOCOL: &str = "ao";
 pub const VARIANT: &str = "ao.TN.1";
 pub const TYPE_MESSAGE: &str = "Message";
 pub const TYPE_PROCESS: &str = "Process";
+pub const TYPE_ASSIGNMENT: &str = "Assignment";
 pub const SDK: &str = "rusty-ao";
 pub const DEFAULT_MU: &str = "https://mu.ao-testnet.xyz";
 pub const DEFAULT_CU: &str = "https://cu.ao-testnet.xyz";
+pub const DEFAULT_SU: &str = "https://su-router.ao-testnet.xyz";
+pub const DEFAULT_GATEWAY: &str = "https://arweave.net";
+pub const DEFAULT_BUNDLER: &str = "https://up.arweave.net";
 
 pub const DEFAULT_MODULE: &str = "xT0ogTeagEGuySbKuUoo_NaWeeBv1fZ4MqgDdKVKY0U";
 pub const DEFAULT_SQLITE_MODULE: &str = "sFNHeYzhHfP9vV9CPpqZMU-4Zzq_qKGKwlwMZozWi2Y";
@@ -22,14 +30,774 @@ pub struct ResponseMu {
     pub message: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Receipt of a data item posted to a bundler
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResponseBundler {
+    pub id: String,
+    #[serde(default)]
+    pub timestamp: Option<u64>,
+}
+
+/// A signed ANS-104 data item, ready to be posted with `Legacy::submit_signed`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignedMessage {
+    /// Message id, the base64url SHA-256 of the signature
+    pub id: String,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "PascalCase")]
 pub struct ResponseCu {
-    pub messages: Vec<serde_json::Value>, // Placeholder for the actual type
-    pub assignments: Vec<serde_json::Value>, // Placeholder for the actual type
-    pub spawns: Vec<serde_json::Value>,   // Placeholder for the actual type
-    pub output: serde_json::Value,        // Placeholder for the actual type
+    #[serde(default)]
+    pub messages: Vec<OutboxMessage>,
+    #[serde(default)]
+    pub assignments: Vec<Assignment>,
+    #[serde(default)]
+    pub spawns: Vec<Spawn>,
+    #[serde(default)]
+    pub output: Output,
+    #[serde(default)]
     pub gas_used: i64,
+    /// Set by the CU when the evaluation threw
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<serde_json::Value>,
+}
+
+impl ResponseCu {
+    /// The error thrown while evaluating the message, either reported at the
+    /// top level of the result or in its output
+    pub fn process_error(&self) -> Option<ProcessError> {
+        let error = match &self.error {
+            Some(serde_json::Value::Null) | None => self.output.error.clone()?,
+            Some(serde_json::Value::String(error)) => error.clone(),
+            Some(error) => error.to_string(),
+        };
+        Some(ProcessError::new(error, self.gas_used))
+    }
+
+    /// Returns the outbox messages carrying a tag `name` equal to `value`
+    pub fn messages_with_tag<'a>(
+        &'a self,
+        name: &'a str,
+        value: &'a str,
+    ) -> impl Iterator<Item = &'a OutboxMessage> + 'a {
+        self.messages
+            .iter()
+            .filter(move |msg| msg.tag(name) == Some(value))
+    }
+
+    /// Returns the first outbox message with the given `Action` tag
+    pub fn find_action(&self, action: &str) -> Option<&OutboxMessage> {
+        self.messages
+            .iter()
+            .find(|msg| msg.action() == Some(action))
+    }
+}
+
+/// A message pushed to the process outbox, as returned by the CU
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct OutboxMessage {
+    #[serde(default)]
+    pub target: String,
+    #[serde(default)]
+    pub anchor: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<Tag>,
+    #[serde(default)]
+    pub data: serde_json::Value,
+}
+
+impl OutboxMessage {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        find_tag(&self.tags, name)
+    }
+
+    pub fn action(&self) -> Option<&str> {
+        self.tag("Action")
+    }
+
+    pub fn data_as<T: DeserializeOwned>(&self) -> Result<T, AoErrors> {
+        data_as(&self.data)
+    }
+}
+
+/// A process spawn requested by the evaluated message
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct Spawn {
+    #[serde(default)]
+    pub anchor: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<Tag>,
+    #[serde(default)]
+    pub data: serde_json::Value,
+}
+
+impl Spawn {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        find_tag(&self.tags, name)
+    }
+
+    pub fn data_as<T: DeserializeOwned>(&self) -> Result<T, AoErrors> {
+        data_as(&self.data)
+    }
+}
+
+/// An assignment of an existing message to other processes
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct Assignment {
+    #[serde(default)]
+    pub processes: Vec<String>,
+    #[serde(default)]
+    pub message: String,
+}
+
+/// The evaluation output. The CU returns either an object or a bare value
+/// (usually an empty string), the latter being stored in `data`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(from = "serde_json::Value")]
+pub struct Output {
+    pub data: serde_json::Value,
+    pub prompt: Option<String>,
+    pub print: Option<bool>,
+    pub error: Option<String>,
+}
+
+impl From<serde_json::Value> for Output {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Object(mut obj) => Self {
+                data: obj.remove("data").unwrap_or_default(),
+                prompt: obj
+                    .remove("prompt")
+                    .and_then(|v| v.as_str().map(str::to_string)),
+                print: obj.remove("print").and_then(|v| v.as_bool()),
+                error: obj.remove("error").map(|v| match v {
+                    serde_json::Value::String(s) => s,
+                    other => other.to_string(),
+                }),
+            },
+            other => Self {
+                data: other,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CronUnit {
+    Milliseconds,
+    Seconds,
+    Minutes,
+    Hours,
+    Days,
+    Months,
+    Years,
+    Blocks,
+}
+
+impl CronUnit {
+    fn as_str(&self, plural: bool) -> &'static str {
+        match (self, plural) {
+            (CronUnit::Milliseconds, false) => "millisecond",
+            (CronUnit::Milliseconds, true) => "milliseconds",
+            (CronUnit::Seconds, false) => "second",
+            (CronUnit::Seconds, true) => "seconds",
+            (CronUnit::Minutes, false) => "minute",
+            (CronUnit::Minutes, true) => "minutes",
+            (CronUnit::Hours, false) => "hour",
+            (CronUnit::Hours, true) => "hours",
+            (CronUnit::Days, false) => "day",
+            (CronUnit::Days, true) => "days",
+            (CronUnit::Months, false) => "month",
+            (CronUnit::Months, true) => "months",
+            (CronUnit::Years, false) => "year",
+            (CronUnit::Years, true) => "years",
+            (CronUnit::Blocks, false) => "block",
+            (CronUnit::Blocks, true) => "blocks",
+        }
+    }
+}
+
+/// A `Cron-Interval` value such as `10-minutes` or `1-block`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CronInterval {
+    pub value: u64,
+    pub unit: CronUnit,
+}
+
+impl CronInterval {
+    pub fn new(value: u64, unit: CronUnit) -> Self {
+        Self { value, unit }
+    }
+}
+
+impl std::fmt::Display for CronInterval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.value, self.unit.as_str(self.value != 1))
+    }
+}
+
+/// Cron settings of a spawned process. `tags` are sent with every tick as
+/// `Cron-Tag-{name}` tags, e.g. `Action` becomes `Cron-Tag-Action`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Cron {
+    pub interval: CronInterval,
+    pub tags: Vec<Tag>,
+}
+
+impl Cron {
+    pub fn new(interval: CronInterval) -> Self {
+        Self {
+            interval,
+            tags: vec![],
+        }
+    }
+
+    pub fn tag(mut self, name: &str, value: &str) -> Self {
+        self.tags.push(Tag::new(name, value));
+        self
+    }
+
+    pub fn to_tags(&self) -> Vec<Tag> {
+        let mut tags = vec![Tag::new("Cron-Interval", &self.interval.to_string())];
+        tags.extend(
+            self.tags
+                .iter()
+                .map(|tag| Tag::new(&format!("Cron-Tag-{}", tag.name), &tag.value)),
+        );
+        tags
+    }
+}
+
+/// Value of the `On-Boot` spawn tag
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum OnBoot {
+    /// Evaluates the spawn message data
+    Data,
+    /// Evaluates the data of an existing transaction
+    TxId(String),
+}
+
+impl OnBoot {
+    pub fn as_tag_value(&self) -> &str {
+        match self {
+            OnBoot::Data => "Data",
+            OnBoot::TxId(id) => id,
+        }
+    }
+}
+
+/// Parameters of a process spawn. Unset data defaults to the current
+/// timestamp, so that every spawn gets a distinct id.
+#[derive(Debug, Clone)]
+pub struct SpawnOptions {
+    pub process_name: String,
+    pub app_name: String,
+    pub module: String,
+    pub scheduler: String,
+    pub data: Option<Vec<u8>>,
+    pub content_type: String,
+    pub on_boot: Option<OnBoot>,
+    pub cron: Option<Cron>,
+    pub tags: Vec<Tag>,
+}
+
+impl SpawnOptions {
+    pub fn new(process_name: String, app_name: String) -> Self {
+        Self {
+            process_name,
+            app_name,
+            module: DEFAULT_MODULE.to_string(),
+            scheduler: DEFAULT_SCHEDULER.to_string(),
+            data: None,
+            content_type: "text/plain".to_string(),
+            on_boot: None,
+            cron: None,
+            tags: vec![],
+        }
+    }
+
+    pub fn module(mut self, module: String) -> Self {
+        self.module = module;
+        self
+    }
+
+    pub fn scheduler(mut self, scheduler: String) -> Self {
+        self.scheduler = scheduler;
+        self
+    }
+
+    pub fn data(mut self, data: Vec<u8>, content_type: String) -> Self {
+        self.data = Some(data);
+        self.content_type = content_type;
+        self
+    }
+
+    /// Sets the Lua code evaluated when the process boots
+    pub fn boot_code(mut self, code: String) -> Self {
+        self.data = Some(code.into_bytes());
+        self.content_type = "text/lua".to_string();
+        self.on_boot = Some(OnBoot::Data);
+        self
+    }
+
+    pub fn on_boot(mut self, on_boot: OnBoot) -> Self {
+        self.on_boot = Some(on_boot);
+        self
+    }
+
+    pub fn cron(mut self, cron: Cron) -> Self {
+        self.cron = Some(cron);
+        self
+    }
+
+    pub fn tag(mut self, name: &str, value: &str) -> Self {
+        self.tags.push(Tag::new(name, value));
+        self
+    }
+
+    /// Spawn specific tags, without the protocol and caller supplied ones
+    pub fn to_tags(&self) -> Vec<Tag> {
+        let mut tags = vec![
+            Tag::new("Name", &self.process_name),
+            Tag::new("App-Name", &self.app_name),
+            Tag::new("Module", &self.module),
+            Tag::new("Scheduler", &self.scheduler),
+            Tag::new("Content-Type", &self.content_type),
+        ];
+        if let Some(on_boot) = &self.on_boot {
+            tags.push(Tag::new("On-Boot", on_boot.as_tag_value()));
+        }
+        if let Some(cron) = &self.cron {
+            tags.extend(cron.to_tags());
+        }
+        tags
+    }
+}
+
+pub const DRY_RUN_PLACEHOLDER_ID: &str = "0000000000000000000000000000000000000000001";
+
+/// Data of a dry run message. The CU expects a string: JSON is sent
+/// encoded and binary data as standard base64.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DryRunData {
+    Text(String),
+    Json(serde_json::Value),
+    Binary(Vec<u8>),
+}
+
+impl Serialize for DryRunData {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            DryRunData::Text(text) => serializer.serialize_str(text),
+            DryRunData::Json(value) => serializer.serialize_str(&value.to_string()),
+            DryRunData::Binary(bytes) => serializer.serialize_str(&BASE64_STANDARD.encode(bytes)),
+        }
+    }
+}
+
+/// A message evaluated by the CU `/dry-run` endpoint. `Id` and `Owner`
+/// default to a placeholder id; set `owner` (or `from`) to simulate a call
+/// from a given wallet.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct DryRunRequest {
+    pub id: String,
+    pub target: String,
+    pub owner: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    pub anchor: Option<String>,
+    pub data: DryRunData,
+    pub tags: Vec<Tag>,
+}
+
+impl DryRunRequest {
+    pub fn new(process_id: String) -> Self {
+        Self {
+            id: DRY_RUN_PLACEHOLDER_ID.to_string(),
+            target: process_id,
+            owner: DRY_RUN_PLACEHOLDER_ID.to_string(),
+            from: None,
+            anchor: None,
+            data: DryRunData::Text("".to_string()),
+            tags: vec![],
+        }
+    }
+
+    pub fn id(mut self, id: String) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub fn owner(mut self, owner: String) -> Self {
+        self.owner = owner;
+        self
+    }
+
+    pub fn from(mut self, from: String) -> Self {
+        self.from = Some(from);
+        self
+    }
+
+    pub fn anchor(mut self, anchor: String) -> Self {
+        self.anchor = Some(anchor);
+        self
+    }
+
+    pub fn data_text(mut self, data: String) -> Self {
+        self.data = DryRunData::Text(data);
+        self
+    }
+
+    pub fn data_json(mut self, data: serde_json::Value) -> Self {
+        self.data = DryRunData::Json(data);
+        self
+    }
+
+    pub fn data_bytes(mut self, data: Vec<u8>) -> Self {
+        self.data = DryRunData::Binary(data);
+        self
+    }
+
+    pub fn action(self, action: &str) -> Self {
+        self.tag("Action", action)
+    }
+
+    pub fn tag(mut self, name: &str, value: &str) -> Self {
+        self.tags.push(Tag::new(name, value));
+        self
+    }
+}
+
+/// A page of the CU `/results/{process}` endpoint
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ResultsPage {
+    pub edges: Vec<ResultEdge>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResultEdge {
+    pub cursor: String,
+    pub node: ResponseCu,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    #[default]
+    #[serde(rename = "ASC")]
+    Ascending,
+    #[serde(rename = "DESC")]
+    Descending,
+}
+
+impl SortOrder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortOrder::Ascending => "ASC",
+            SortOrder::Descending => "DESC",
+        }
+    }
+}
+
+/// Cursors and ordering for the CU `/results/{process}` endpoint
+#[derive(Debug, Clone, Default)]
+pub struct ResultsQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub sort: SortOrder,
+    pub limit: Option<u32>,
+}
+
+impl ResultsQuery {
+    pub fn to_params(&self) -> Vec<(&'static str, String)> {
+        let mut params = vec![("sort", self.sort.as_str().to_string())];
+        if let Some(from) = &self.from {
+            params.push(("from", from.clone()));
+        }
+        if let Some(to) = &self.to {
+            params.push(("to", to.clone()));
+        }
+        if let Some(limit) = self.limit {
+            params.push(("limit", limit.to_string()));
+        }
+        params
+    }
+}
+
+/// A page of a process schedule, as returned by the SU
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SchedulePage {
+    #[serde(default)]
+    pub page_info: PageInfo,
+    pub edges: Vec<ScheduleEdge>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PageInfo {
+    pub has_next_page: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScheduleEdge {
+    pub cursor: String,
+    pub node: ScheduledMessage,
+}
+
+/// A message along with the assignment that placed it in the schedule
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScheduledMessage {
+    #[serde(default)]
+    pub message: Option<SuItem>,
+    pub assignment: SuItem,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SuItem {
+    pub id: String,
+    #[serde(default)]
+    pub owner: Option<SuOwner>,
+    #[serde(default)]
+    pub tags: Vec<Tag>,
+    #[serde(default)]
+    pub data: Option<String>,
+    #[serde(default)]
+    pub signature: Option<String>,
+    #[serde(default)]
+    pub anchor: Option<String>,
+    #[serde(default)]
+    pub target: Option<String>,
 }
 
-pub use bundlr_sdk::tags::Tag;
\ No newline at end of file
+impl SuItem {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        find_tag(&self.tags, name)
+    }
+
+    /// Position of the message in the process schedule (assignments only)
+    pub fn nonce(&self) -> Option<u64> {
+        self.tag("Nonce").and_then(|v| v.parse().ok())
+    }
+
+    pub fn timestamp(&self) -> Option<u64> {
+        self.tag("Timestamp").and_then(|v| v.parse().ok())
+    }
+
+    pub fn block_height(&self) -> Option<u64> {
+        self.tag("Block-Height").and_then(|v| v.parse().ok())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SuOwner {
+    pub address: String,
+    pub key: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SuTimestamp {
+    pub timestamp: u64,
+    pub block_height: u64,
+}
+
+/// Bounds of a process schedule query, `from` and `to` being SU cursors
+#[derive(Debug, Clone, Default)]
+pub struct ScheduleQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub limit: Option<u32>,
+}
+
+impl ScheduleQuery {
+    pub fn to_params(&self) -> Vec<(&'static str, String)> {
+        let mut params = vec![];
+        if let Some(from) = &self.from {
+            params.push(("from", from.clone()));
+        }
+        if let Some(to) = &self.to {
+            params.push(("to", to.clone()));
+        }
+        if let Some(limit) = self.limit {
+            params.push(("limit", limit.to_string()));
+        }
+        params
+    }
+}
+
+fn find_tag<'a>(tags: &'a [Tag], name: &str) -> Option<&'a str> {
+    tags.iter()
+        .find(|tag| tag.name == name)
+        .map(|tag| tag.value.as_str())
+}
+
+// Data is usually a JSON-encoded string, but may also be inlined as JSON
+fn data_as<T: DeserializeOwned>(data: &serde_json::Value) -> Result<T, AoErrors> {
+    match data {
+        serde_json::Value::String(s) => serde_json::from_str(s),
+        other => T::deserialize(other),
+    }
+    .map_err(|e| AoErrors::InvalidMessageData {
+        source: Some(e.into()),
+    })
+}
+
+pub use bundlr_sdk::tags::Tag;
+#[cfg(test)]
+mod tests {
+    use crate::errors::AoErrors;
+    use crate::scheme::{
+        Cron, CronInterval, CronUnit, DryRunRequest, ResponseCu, ResultsPage, ResultsQuery,
+        SortOrder, SpawnOptions, Tag,
+    };
+    use serde::Deserialize;
+
+    const RESULT: &str = r#"{
+        "Messages": [{
+            "Target": "xU9zFkq3X2ZQ6olwNVvr1vUWIjc3kXTWr7xKQD6dh10",
+            "Anchor": "00000000000000000000000000000001",
+            "Tags": [{"name": "Action", "value": "Credit-Notice"}, {"name": "Quantity", "value": "10"}],
+            "Data": "{\"balance\":\"10\"}"
+        }],
+        "Assignments": [],
+        "Spawns": [],
+        "Output": {"data": "ok", "prompt": "aos> ", "print": true},
+        "GasUsed": 0
+    }"#;
+
+    #[derive(Deserialize)]
+    struct Balance {
+        balance: String,
+    }
+
+    #[test]
+    pub fn test_response_cu_messages() {
+        let res: ResponseCu = serde_json::from_str(RESULT).unwrap();
+        let msg = res.find_action("Credit-Notice").unwrap();
+        assert_eq!(msg.tag("Quantity"), Some("10"));
+        assert_eq!(msg.data_as::<Balance>().unwrap().balance, "10");
+        #[derive(Debug, Deserialize)]
+        struct Transfer {
+            #[allow(dead_code)]
+            recipient: String,
+        }
+        let err = msg.data_as::<Transfer>().unwrap_err();
+        assert!(err.to_string().contains("missing field `recipient`"));
+        assert_eq!(res.output.prompt.as_deref(), Some("aos> "));
+        assert_eq!(res.output.print, Some(true));
+    }
+
+    #[test]
+    pub fn test_response_cu_bare_output() {
+        let res: ResponseCu = serde_json::from_str(
+            r#"{"Messages": [], "Assignments": [], "Spawns": [], "Output": "", "GasUsed": 0}"#,
+        )
+        .unwrap();
+        assert_eq!(res.output.data, serde_json::Value::String("".to_string()));
+        assert!(res.output.prompt.is_none());
+    }
+
+    #[test]
+    pub fn test_response_cu_process_error() {
+        let res: ResponseCu = serde_json::from_str(
+            r#"{"Messages": [], "Spawns": [], "Output": "", "GasUsed": 1532, "Error": "[string \"aos\"]:12: insufficient balance\nstack traceback:\n\t[string \"aos\"]:12: in function 'transfer'"}"#,
+        )
+        .unwrap();
+        let error = res.process_error().unwrap();
+        assert_eq!(error.message, "[string \"aos\"]:12: insufficient balance");
+        assert_eq!(
+            error.traceback.as_deref(),
+            Some("[string \"aos\"]:12: in function 'transfer'")
+        );
+        assert_eq!(error.gas_used, 1532);
+
+        match AoErrors::check_result(res) {
+            Err(AoErrors::ProcessFailed { response, .. }) => assert_eq!(response.gas_used, 1532),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let res: ResponseCu = serde_json::from_str(
+            r#"{"Messages": [], "Spawns": [], "Output": {"data": "", "error": "boom"}, "GasUsed": 0}"#,
+        )
+        .unwrap();
+        assert_eq!(res.process_error().unwrap().message, "boom");
+
+        let res: ResponseCu = serde_json::from_str(RESULT).unwrap();
+        assert!(res.process_error().is_none());
+    }
+
+    #[test]
+    pub fn test_results_page() {
+        let page: ResultsPage = serde_json::from_str(
+            r#"{"edges": [{"cursor": "eyJ0aW1lc3RhbXAiOjF9", "node": {"Messages": [], "Spawns": [], "Output": {"data": "1"}, "GasUsed": 0}}]}"#,
+        )
+        .unwrap();
+        assert_eq!(page.edges.len(), 1);
+        assert_eq!(page.edges[0].cursor, "eyJ0aW1lc3RhbXAiOjF9");
+        assert!(page.edges[0].node.assignments.is_empty());
+
+        let query = ResultsQuery {
+            from: Some("eyJ0aW1lc3RhbXAiOjF9".to_string()),
+            sort: SortOrder::Descending,
+            limit: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(
+            query.to_params(),
+            vec![
+                ("sort", "DESC".to_string()),
+                ("from", "eyJ0aW1lc3RhbXAiOjF9".to_string()),
+                ("limit", "10".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_cron_tags() {
+        assert_eq!(CronInterval::new(1, CronUnit::Minutes).to_string(), "1-minute");
+        assert_eq!(CronInterval::new(10, CronUnit::Blocks).to_string(), "10-blocks");
+
+        let cron = Cron::new(CronInterval::new(5, CronUnit::Seconds)).tag("Action", "Tick");
+        assert_eq!(
+            cron.to_tags(),
+            vec![
+                Tag::new("Cron-Interval", "5-seconds"),
+                Tag::new("Cron-Tag-Action", "Tick"),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_spawn_options_boot_code() {
+        let options = SpawnOptions::new("test1".to_string(), "rusty-ao".to_string())
+            .boot_code("Handlers.add('ping', 'Ping', function(msg) end)".to_string())
+            .cron(Cron::new(CronInterval::new(1, CronUnit::Hours)));
+        let tags = options.to_tags();
+
+        assert!(tags.contains(&Tag::new("On-Boot", "Data")));
+        assert!(tags.contains(&Tag::new("Content-Type", "text/lua")));
+        assert!(tags.contains(&Tag::new("Cron-Interval", "1-hour")));
+        assert!(options.data.is_some());
+    }
+
+    #[test]
+    pub fn test_dry_run_request() {
+        let request = DryRunRequest::new("xU9zFkq3X2ZQ6olwNVvr1vUWIjc3kXTWr7xKQD6dh10".to_string())
+            .owner("vh-NTHVvlKZqRxc8LyyTNok65yQ55a_PJ1zWLb9G2JI".to_string())
+            .action("Balance")
+            .data_json(serde_json::json!({ "target": "me" }));
+        let json = serde_json::to_value(&request).unwrap();
+
+        assert_eq!(json["Owner"], "vh-NTHVvlKZqRxc8LyyTNok65yQ55a_PJ1zWLb9G2JI");
+        assert_eq!(json["Target"], "xU9zFkq3X2ZQ6olwNVvr1vUWIjc3kXTWr7xKQD6dh10");
+        assert_eq!(json["Data"], r#"{"target":"me"}"#);
+        assert!(json.get("From").is_none());
+
+        let json = serde_json::to_value(request.data_bytes(vec![0, 1, 2])).unwrap();
+        assert_eq!(json["Data"], "AAEC");
+    }
+}
@@ -1,44 +1,87 @@
+use crate::errors::{AoErrors, HbErrors};
 use crate::utils::get_node;
-use crate::hyperbeam::Hyperbeam;
+use crate::hyperbeam::{Hyperbeam, HyperbeamBuilder};
+use crate::signer::{AoSigner, WalletSigner};
 use crate::wallet::SignerTypes;
-use axum::{extract::Path, response::Json};
-use serde::{Deserialize, Serialize};
+use axum::extract::{Path, State};
+use axum::response::Json;
 use serde_json::{Value, json};
 use axum::response::IntoResponse;
 use axum::{http::StatusCode};
 use pulldown_cmark::{Parser, Options, html};
+use std::sync::Arc;
 
+/// Shared by all the handlers, the signer is loaded and the HTTP client
+/// (pooling node connections) is built once at startup
+#[derive(Clone)]
+pub struct AppState {
+    signer: Arc<dyn AoSigner>,
+    client: reqwest::Client,
+}
+
+impl AppState {
+    /// Loads the wallet of `AO_WALLET` (see `SignerTypes::from_env`). It is
+    /// required, only debug builds fall back to `test_key.json` for local
+    /// runs.
+    pub fn from_env() -> Result<Self, AoErrors> {
+        let wallet = match std::env::var_os("AO_WALLET") {
+            None if cfg!(debug_assertions) => SignerTypes::Arweave("test_key.json".to_string()),
+            _ => SignerTypes::from_env("AO_WALLET")?,
+        };
+        let signer = WalletSigner::load(&wallet)
+            .map_err(|e| AoErrors::ErrorConstructingSigner { source: Some(e) })?;
+        Ok(Self {
+            signer: Arc::new(signer),
+            client: reqwest::Client::new(),
+        })
+    }
+
+    fn hyperbeam(&self, node_endpoint: String) -> Result<Hyperbeam, HbErrors> {
+        HyperbeamBuilder::with_signer(self.signer.clone())
+            .node_endpoint(node_endpoint)
+            .client(self.client.clone())
+            .build()
+    }
+}
 
-fn init_hb_client(node_endpoint: String) -> Hyperbeam {
-    Hyperbeam::new(node_endpoint, SignerTypes::Arweave("test_key.json".to_string())).unwrap()
+// node failures are reported as a bad gateway
+fn bad_gateway(error: HbErrors) -> (StatusCode, String) {
+    (StatusCode::BAD_GATEWAY, error.to_string())
 }
 
 pub async fn status_handler() -> Json<Value> {
     Json(json!({"status": "running"}))
 }
 
-pub async fn node_info_handler(Path(id): Path<String>) -> Json<Value> {
+pub async fn node_info_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, (StatusCode, String)> {
     let node_obj = get_node(&id);
-    let hb = init_hb_client(node_obj.node_url);
-    let res = hb.meta_info().await.unwrap();
+    let hb = state.hyperbeam(node_obj.node_url).map_err(bad_gateway)?;
+    let res = hb.meta_info().await.map_err(bad_gateway)?;
 
-    Json(serde_json::to_value(res).unwrap())
+    Ok(Json(res))
 }
 
-pub async fn node_routes_handler(Path(id): Path<String>) -> Json<Value> {
+pub async fn node_routes_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, (StatusCode, String)> {
     let node_obj = get_node(&id);
-    let hb = init_hb_client(node_obj.node_url);
-    let res = hb.router_routes().await.unwrap();
-    
-    Json(serde_json::to_value(res).unwrap())
+    let hb = state.hyperbeam(node_obj.node_url).map_err(bad_gateway)?;
+    let res = hb.router_routes().await.map_err(bad_gateway)?;
+
+    Ok(Json(res))
 }
 
 pub async fn node_metrics_handler(
+    State(state): State<AppState>,
     Path(id): Path<String>,
-) -> impl IntoResponse {
+) -> Result<impl IntoResponse, (StatusCode, String)> {
     let node_obj = get_node(&id);
-    let hb = init_hb_client(node_obj.node_url);
-    let markdown_content = hb.hyperbuddy_metrics().await.unwrap();
+    let hb = state.hyperbeam(node_obj.node_url).map_err(bad_gateway)?;
+    let markdown_content = hb.hyperbuddy_metrics().await.map_err(bad_gateway)?;
 
     let mut options = Options::empty();
     options.insert(Options::ENABLE_TABLES);
@@ -119,6 +162,6 @@ pub async fn node_metrics_handler(
         .header("accept-ranges", "bytes")
         .header("cache-control", "public, max-age=31536000")
         .body(styled_html)
-        .unwrap()
-        .into_response()
+        .map(IntoResponse::into_response)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
 }
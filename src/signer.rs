@@ -0,0 +1,221 @@
+use crate::errors::{AoErrors, BoxError};
+use crate::wallet::{ethereum_address, SignerTypes, Signers};
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use base64::Engine;
+use bundlr_sdk::Signer;
+use futures::future::BoxFuture;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+/// Signs ANS-104 data items on behalf of `Legacy` and `Hyperbeam`. Implement
+/// it to sign with a remote KMS, an HSM-backed key or a fixed test key; the
+/// bundlr signers and loaded wallets (`WalletSigner`) implement it already.
+pub trait AoSigner: Send + Sync {
+    /// ANS-104 signature type: 1 for Arweave, 2 for ED25519, 3 for Ethereum
+    /// and 4 for Solana
+    fn signature_type(&self) -> u16;
+
+    fn signature_length(&self) -> usize;
+
+    /// The public key set as the `Owner` of the signed data items
+    fn owner(&self) -> Vec<u8>;
+
+    /// Signs the ANS-104 deep hash of a data item
+    fn sign<'a>(&'a self, message: Vec<u8>) -> BoxFuture<'a, Result<Vec<u8>, BoxError>>;
+
+    /// The native address of the owner
+    fn address(&self) -> String {
+        address(self.signature_type(), &self.owner())
+    }
+}
+
+/// The address of a data item owner: the base64url SHA-256 of the RSA
+/// modulus for Arweave, base58 for ED25519 and Solana keys and EIP-55 for
+/// Ethereum keys
+pub fn address(signature_type: u16, owner: &[u8]) -> String {
+    match signature_type {
+        2 | 4 => bs58::encode(owner).into_string(),
+        3 => ethereum_address(owner),
+        _ => BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(owner)),
+    }
+}
+
+impl<T: Signer> AoSigner for T {
+    fn signature_type(&self) -> u16 {
+        self.sig_type().as_u16()
+    }
+
+    fn signature_length(&self) -> usize {
+        self.get_sig_length() as usize
+    }
+
+    fn owner(&self) -> Vec<u8> {
+        self.pub_key().to_vec()
+    }
+
+    fn sign<'a>(&'a self, message: Vec<u8>) -> BoxFuture<'a, Result<Vec<u8>, BoxError>> {
+        Box::pin(async move { Ok(Signer::sign(self, message.into())?.to_vec()) })
+    }
+}
+
+/// A loaded wallet. Its bundlr signer is resolved when it is created, so a
+/// wallet without a private key fails there rather than signing with an
+/// empty owner.
+pub struct WalletSigner {
+    wallet: Signers,
+    signature_type: u16,
+    signature_length: usize,
+    owner: Vec<u8>,
+}
+
+impl WalletSigner {
+    pub fn new(wallet: Signers) -> Result<Self, AoErrors> {
+        let signer = wallet.raw_signer()?;
+        let signature_type = signer.sig_type().as_u16();
+        let signature_length = signer.get_sig_length() as usize;
+        let owner = signer.pub_key().to_vec();
+        Ok(Self {
+            wallet,
+            signature_type,
+            signature_length,
+            owner,
+        })
+    }
+
+    pub(crate) fn load(wallet: &SignerTypes) -> Result<Self, BoxError> {
+        Ok(Self::new(wallet.signer()?)?)
+    }
+
+    pub fn wallet(&self) -> &Signers {
+        &self.wallet
+    }
+}
+
+impl AoSigner for WalletSigner {
+    fn signature_type(&self) -> u16 {
+        self.signature_type
+    }
+
+    fn signature_length(&self) -> usize {
+        self.signature_length
+    }
+
+    fn owner(&self) -> Vec<u8> {
+        self.owner.clone()
+    }
+
+    fn sign<'a>(&'a self, message: Vec<u8>) -> BoxFuture<'a, Result<Vec<u8>, BoxError>> {
+        Box::pin(async move { Ok(self.wallet.raw_signer()?.sign(message.into())?.to_vec()) })
+    }
+}
+
+/// The signer a `LegacyBuilder` or `HyperbeamBuilder` was given
+#[derive(Clone)]
+pub(crate) enum SignerSource {
+    Wallet(SignerTypes),
+    Custom(Arc<dyn AoSigner>),
+}
+
+impl SignerSource {
+    pub(crate) fn build(self) -> Result<Arc<dyn AoSigner>, BoxError> {
+        match self {
+            SignerSource::Wallet(wallet) => Ok(Arc::new(WalletSigner::load(&wallet)?)),
+            SignerSource::Custom(signer) => Ok(signer),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ao::LegacyBuilder;
+    use crate::errors::{AoErrors, BoxError};
+    use crate::scheme::Tag;
+    use crate::signer::{AoSigner, WalletSigner};
+    use crate::wallet::SignerTypes;
+    use bundlr_sdk::Secp256k1Signer;
+    use futures::future::BoxFuture;
+    use secp256k1::SecretKey;
+    use std::sync::Arc;
+
+    // stands in for a remote signer, without any key file
+    struct TestSigner {
+        key: Secp256k1Signer,
+        truncate: bool,
+    }
+
+    impl TestSigner {
+        fn new(truncate: bool) -> Self {
+            Self {
+                key: Secp256k1Signer::new(SecretKey::from_slice(&[7u8; 32]).unwrap()),
+                truncate,
+            }
+        }
+    }
+
+    impl AoSigner for TestSigner {
+        fn signature_type(&self) -> u16 {
+            3
+        }
+
+        fn signature_length(&self) -> usize {
+            65
+        }
+
+        fn owner(&self) -> Vec<u8> {
+            self.key.owner()
+        }
+
+        fn sign<'a>(&'a self, message: Vec<u8>) -> BoxFuture<'a, Result<Vec<u8>, BoxError>> {
+            Box::pin(async move {
+                let mut signature = self.key.sign(message).await?;
+                if self.truncate {
+                    signature.pop();
+                }
+                Ok(signature)
+            })
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_custom_signer() {
+        let ao = LegacyBuilder::with_signer(Arc::new(TestSigner::new(false)))
+            .build()
+            .unwrap();
+        assert_eq!(ao.address(), TestSigner::new(false).key.address());
+        assert!(ao.address().starts_with("0x"));
+
+        let mut tx = ao
+            .sign_bundle_item(
+                "xU9zFkq3X2ZQ6olwNVvr1vUWIjc3kXTWr7xKQD6dh10".to_string(),
+                None,
+                b"hello".to_vec(),
+                vec![Tag::new("Action", "Ping")],
+            )
+            .await
+            .unwrap();
+        assert!(tx.verify().await.is_ok());
+
+        let ao = LegacyBuilder::with_signer(Arc::new(TestSigner::new(true)))
+            .build()
+            .unwrap();
+        let res = ao
+            .sign_bundle_item(String::new(), None, b"hello".to_vec(), vec![])
+            .await;
+        match res {
+            Err(AoErrors::InvalidSigner { source }) => {
+                assert!(source.unwrap().to_string().contains("signature is 64 bytes"))
+            }
+            _ => panic!("expected InvalidSigner"),
+        }
+    }
+
+    #[test]
+    pub fn test_wallet_signer() {
+        let wallet = SignerTypes::Arweave("test_key.json".to_string());
+        let signer = WalletSigner::load(&wallet).unwrap();
+        let key = signer.wallet().raw_signer().unwrap();
+        assert_eq!(signer.signature_type(), key.sig_type().as_u16());
+        assert_eq!(signer.signature_length(), 512);
+        assert_eq!(signer.owner(), key.pub_key().to_vec());
+    }
+}
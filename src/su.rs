@@ -0,0 +1,147 @@
+use crate::errors::{response_json, AoErrors, HttpError};
+use crate::scheme::{
+    ScheduleEdge, SchedulePage, ScheduleQuery, ScheduledMessage, SuTimestamp, DEFAULT_SU,
+};
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+
+/// Scheduler Unit client, reading process schedules and assignments
+pub struct Su {
+    su_url: String,
+    client: Client,
+}
+
+impl Su {
+    pub fn new(su_url: String) -> Self {
+        Self::with_client(su_url, Client::new())
+    }
+
+    pub fn default_init() -> Self {
+        Self::new(DEFAULT_SU.to_string())
+    }
+
+    /// Creates a client sharing an existing `reqwest::Client`, e.g. the one
+    /// of a `Legacy` instance
+    pub fn with_client(su_url: String, client: Client) -> Self {
+        Self { su_url, client }
+    }
+
+    async fn get_json<T: DeserializeOwned>(
+        &self,
+        path: String,
+        params: &[(&str, String)],
+    ) -> Result<T, AoErrors> {
+        let url = format!("{}/{}", self.su_url, path);
+        let res = self
+            .client
+            .get(&url)
+            .query(params)
+            .send()
+            .await
+            .map_err(|e| AoErrors::InvalidServerResponse(HttpError::request(&url, e)))?;
+
+        response_json(res).await
+    }
+
+    /// Fetches a single page of the process schedule, in assignment order
+    pub async fn schedule(
+        &self,
+        process_id: String,
+        query: &ScheduleQuery,
+    ) -> Result<SchedulePage, AoErrors> {
+        let mut params = query.to_params();
+        params.push(("process-id", process_id.clone()));
+        self.get_json(process_id, &params).await
+    }
+
+    /// Streams the whole process schedule, following the page cursors while
+    /// the SU reports a next page.
+    pub fn schedule_stream(
+        &self,
+        process_id: String,
+        query: ScheduleQuery,
+    ) -> BoxStream<'_, Result<ScheduleEdge, AoErrors>> {
+        futures::stream::try_unfold(Some(query), move |query| {
+            let process_id = process_id.clone();
+            async move {
+                let Some(query) = query else {
+                    return Ok(None);
+                };
+                let page = self.schedule(process_id, &query).await?;
+                let next = page
+                    .edges
+                    .last()
+                    .filter(|_| page.page_info.has_next_page)
+                    .filter(|edge| query.from.as_ref() != Some(&edge.cursor))
+                    .map(|edge| ScheduleQuery {
+                        from: Some(edge.cursor.clone()),
+                        ..query.clone()
+                    });
+                Ok(Some((page.edges, next)))
+            }
+        })
+        .map_ok(|edges| futures::stream::iter(edges.into_iter().map(Ok)))
+        .try_flatten()
+        .boxed()
+    }
+
+    /// Fetches a single message along with its assignment
+    pub async fn message(
+        &self,
+        process_id: String,
+        message_id: String,
+    ) -> Result<ScheduledMessage, AoErrors> {
+        self.get_json(message_id, &[("process-id", process_id)])
+            .await
+    }
+
+    /// Returns the latest timestamp and block height known by the SU
+    pub async fn timestamp(&self, process_id: String) -> Result<SuTimestamp, AoErrors> {
+        self.get_json("timestamp".to_string(), &[("process-id", process_id)])
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scheme::SchedulePage;
+
+    #[test]
+    pub fn test_schedule_page() {
+        let page: SchedulePage = serde_json::from_str(
+            r#"{
+                "page_info": {"has_next_page": true},
+                "edges": [{
+                    "cursor": "1717000000000",
+                    "node": {
+                        "message": {
+                            "id": "5JtjkYy1hk0Zce5mP6gDWIOdt9rCSQAFX-K9jZnqniw",
+                            "owner": {"address": "vh-NTHVvlKZqRxc8LyyTNok65yQ55a_PJ1zWLb9G2JI", "key": "key"},
+                            "tags": [{"name": "Action", "value": "Eval"}],
+                            "data": "return 1",
+                            "target": "ya9XinY0qXeYyf7HXANqzOiKns8yiXZoDtFqUMXkX0Q"
+                        },
+                        "assignment": {
+                            "id": "assignment",
+                            "tags": [
+                                {"name": "Nonce", "value": "42"},
+                                {"name": "Timestamp", "value": "1717000000000"},
+                                {"name": "Block-Height", "value": "1430000"}
+                            ]
+                        }
+                    }
+                }]
+            }"#,
+        )
+        .unwrap();
+
+        assert!(page.page_info.has_next_page);
+        let node = &page.edges[0].node;
+        assert_eq!(node.message.as_ref().unwrap().tag("Action"), Some("Eval"));
+        assert_eq!(node.assignment.nonce(), Some(42));
+        assert_eq!(node.assignment.timestamp(), Some(1717000000000));
+        assert_eq!(node.assignment.block_height(), Some(1430000));
+    }
+}
@@ -0,0 +1,600 @@
+use crate::ao::Legacy;
+use crate::data_item::DataItem;
+use crate::hyperbeam::Hyperbeam;
+use crate::su::Su;
+use crate::wallet::SignerTypes;
+use axum::body::{Body, Bytes};
+use axum::extract::State;
+use axum::http::{HeaderMap, Method, Uri};
+use axum::response::Response;
+use axum::Router;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use base64::Engine;
+use reqwest::Url;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+/// Address the mock HyperBEAM node reports
+pub const MOCK_NODE_ADDRESS: &str = "mock-hyperbeam-node-address-000000000000000";
+
+const EMPTY_RESULT: &str = r#"{"Messages":[],"Spawns":[],"Assignments":[],"Output":"","GasUsed":0}"#;
+
+/// A response scripted with `MockServer::respond`
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// Time waited before answering, e.g. to trigger client timeouts
+    pub delay: Option<Duration>,
+}
+
+impl MockResponse {
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: vec![],
+            delay: None,
+        }
+    }
+
+    pub fn json(value: Value) -> Self {
+        Self::new(200)
+            .header("content-type", "application/json")
+            .body(value.to_string())
+    }
+
+    pub fn text(body: &str) -> Self {
+        Self::new(200).body(body)
+    }
+
+    /// A 307 to `location`, as sent by the CU router
+    pub fn redirect(location: &str) -> Self {
+        Self::new(307).header("location", location)
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+}
+
+/// A request received by a `MockServer`
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub method: Method,
+    pub path: String,
+    pub query: Option<String>,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl MockRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)?.to_str().ok()
+    }
+
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        let url = Url::parse(&format!("http://mock/?{}", self.query.as_deref()?)).ok()?;
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    }
+}
+
+#[derive(Default)]
+struct MockState {
+    scripted: VecDeque<(Method, String, MockResponse)>,
+    requests: Vec<MockRequest>,
+    items: Vec<DataItem>,
+    // (process id, timestamp, message) assigned by the SU, in nonce order
+    schedule: Vec<(String, u64, DataItem)>,
+    // (process id, message id, result) in submission order
+    results: Vec<(String, String, Value)>,
+    dry_runs: HashMap<String, Value>,
+    states: HashMap<String, Value>,
+    redirects: HashMap<String, String>,
+}
+
+/// An in-process MU, CU, SU, bundler and HyperBEAM node for offline tests.
+///
+/// Data items posted to the MU (`POST /`) or the bundler (`POST /tx`) are
+/// parsed and kept, CU results and dry runs answer with what was set for
+/// them (an empty result otherwise), and any request can be scripted with
+/// `respond`. Messages posted to the MU are assigned to their target
+/// process, which the SU serves with `GET /{process_id}` (the schedule,
+/// cursors being nonces) and `GET /{message_id}?process-id=`. The server
+/// stops when dropped.
+pub struct MockServer {
+    url: String,
+    state: Arc<Mutex<MockState>>,
+    server: JoinHandle<()>,
+}
+
+impl MockServer {
+    pub async fn start() -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let state = Arc::new(Mutex::new(MockState::default()));
+
+        let app = Router::new().fallback(handle).with_state(state.clone());
+        let server = tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+
+        Ok(Self { url, state, server })
+    }
+
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    /// A `Legacy` client using this server as MU, CU and bundler
+    pub fn legacy(&self, signer: SignerTypes) -> Legacy {
+        Legacy::builder(signer)
+            .mu_url(self.url())
+            .cu_url(self.url())
+            .bundler_url(self.url())
+            .build()
+            .unwrap()
+    }
+
+    pub fn hyperbeam(&self, signer: SignerTypes) -> Hyperbeam {
+        Hyperbeam::new(self.url(), signer).unwrap()
+    }
+
+    pub fn su(&self) -> Su {
+        Su::new(self.url())
+    }
+
+    /// Answers the next `method` request to `path` (without the query) with
+    /// `response`. Scripted responses are used once, in order.
+    pub fn respond(&self, method: Method, path: &str, response: MockResponse) {
+        self.state
+            .lock()
+            .unwrap()
+            .scripted
+            .push_back((method, path.to_string(), response));
+    }
+
+    /// The CU result of `message_id`, also listed by `/results/{process_id}`
+    pub fn set_result(&self, process_id: &str, message_id: &str, result: Value) {
+        self.state.lock().unwrap().results.push((
+            process_id.to_string(),
+            message_id.to_string(),
+            result,
+        ));
+    }
+
+    pub fn set_dry_run(&self, process_id: &str, result: Value) {
+        self.state
+            .lock()
+            .unwrap()
+            .dry_runs
+            .insert(process_id.to_string(), result);
+    }
+
+    /// The state returned by HyperBEAM `/{process_id}~process@1.0/now`
+    pub fn set_process_state(&self, process_id: &str, state: Value) {
+        self.state
+            .lock()
+            .unwrap()
+            .states
+            .insert(process_id.to_string(), state);
+    }
+
+    /// Redirects the CU requests of `process_id` to the same path on `cu_url`
+    /// with a 307, like the CU router does
+    pub fn redirect_process(&self, process_id: &str, cu_url: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .redirects
+            .insert(process_id.to_string(), cu_url.to_string());
+    }
+
+    /// Every request received so far
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// The data items posted to the MU and the bundler
+    pub fn received_items(&self) -> Vec<DataItem> {
+        self.state.lock().unwrap().items.clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+async fn handle(
+    State(state): State<Arc<Mutex<MockState>>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let request = MockRequest {
+        method,
+        path: uri.path().to_string(),
+        query: uri.query().map(str::to_string),
+        headers,
+        body: body.to_vec(),
+    };
+
+    let response = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(request.clone());
+        let scripted = state
+            .scripted
+            .iter()
+            .position(|(method, path, _)| *method == request.method && *path == request.path);
+        match scripted.and_then(|i| state.scripted.remove(i)) {
+            Some((_, _, response)) => response,
+            None => state.route(&request),
+        }
+    };
+    if let Some(delay) = response.delay {
+        tokio::time::sleep(delay).await;
+    }
+
+    let mut builder = Response::builder().status(response.status);
+    for (name, value) in &response.headers {
+        builder = builder.header(name, value);
+    }
+    builder.body(Body::from(response.body)).unwrap()
+}
+
+impl MockState {
+    fn route(&mut self, request: &MockRequest) -> MockResponse {
+        let path = request.path.as_str();
+        let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+
+        // the process id of a CU request
+        let process_id = match segments.as_slice() {
+            ["results" | "cron", process_id] => Some(process_id.to_string()),
+            ["result", _] | ["dry-run"] => request.query_param("process-id"),
+            _ => None,
+        };
+        if let Some(cu_url) = process_id.as_ref().and_then(|id| self.redirects.get(id)) {
+            let query = request
+                .query
+                .as_ref()
+                .map(|query| format!("?{}", query))
+                .unwrap_or_default();
+            return MockResponse::redirect(&format!("{}{}{}", cu_url, path, query));
+        }
+
+        match (&request.method, segments.as_slice()) {
+            (&Method::POST, [""]) => {
+                let response = self.receive(&request.body, |item| {
+                    json!({"id": item.id(), "message": "Processing DataItem"})
+                });
+                if response.status == 200 {
+                    self.assign();
+                }
+                response
+            }
+            (&Method::POST, ["tx"]) => self.receive(&request.body, |item| {
+                json!({"id": item.id(), "timestamp": now()})
+            }),
+            (&Method::POST | &Method::DELETE, ["monitor", _]) => MockResponse::new(200),
+            (&Method::GET, ["result", message_id]) => {
+                let result = self
+                    .results
+                    .iter()
+                    .find(|(_, id, _)| id == message_id)
+                    .map(|(_, _, result)| result.clone());
+                MockResponse::json(result.unwrap_or_else(empty_result))
+            }
+            (&Method::GET, ["results", process_id]) => self.results_page(process_id, request),
+            (&Method::GET, ["cron", _]) => MockResponse::json(json!({"edges": []})),
+            (&Method::POST, ["dry-run"]) => {
+                let result = process_id.and_then(|id| self.dry_runs.get(&id).cloned());
+                MockResponse::json(result.unwrap_or_else(empty_result))
+            }
+            (&Method::GET, ["timestamp"]) => {
+                MockResponse::json(json!({"timestamp": now(), "block_height": 0}))
+            }
+            (&Method::GET, ["~meta@1.0", "info", "serialize~json@1.0"]) => {
+                MockResponse::json(json!({"address": MOCK_NODE_ADDRESS}))
+            }
+            (&Method::GET, ["~meta@1.0", "info", "address"]) => {
+                MockResponse::text(MOCK_NODE_ADDRESS)
+            }
+            (&Method::GET, ["~router@1.0", "routes", "serialize~json@1.0"]) => {
+                MockResponse::json(json!([]))
+            }
+            (&Method::GET, ["~hyperbuddy@1.0", "index", "~hyperbuddy@1.0", "metrics"]) => {
+                MockResponse::text("# mock HyperBEAM node metrics\n")
+            }
+            (&Method::GET, [process, "now"]) if process.ends_with("~process@1.0") => {
+                let process_id = process.trim_end_matches("~process@1.0");
+                match self.states.get(process_id) {
+                    Some(state) => MockResponse::text(&format!(
+                        "--mock\r\ncontent-disposition: form-data;name=\"overview/data\"\r\n\r\n{}\r\n--mock--\r\n",
+                        state
+                    )),
+                    None => MockResponse::new(404),
+                }
+            }
+            (&Method::GET, [id]) => match request.query_param("process-id") {
+                Some(process_id) if process_id == *id => self.schedule_page(id, request),
+                Some(process_id) => self
+                    .schedule
+                    .iter()
+                    .filter(|(pid, _, _)| *pid == process_id)
+                    .enumerate()
+                    .find(|(_, (_, _, item))| item.id() == *id)
+                    .map(|(nonce, scheduled)| {
+                        MockResponse::json(scheduled_json(nonce, scheduled))
+                    })
+                    .unwrap_or_else(|| MockResponse::new(404)),
+                None => MockResponse::new(404),
+            },
+            _ => MockResponse::new(404),
+        }
+    }
+
+    // schedules the last received item on its target process
+    fn assign(&mut self) {
+        let item = self.items.last().unwrap();
+        if let Some(process_id) = item.target() {
+            self.schedule.push((process_id, now(), item.clone()));
+        }
+    }
+
+    fn schedule_page(&self, process_id: &str, request: &MockRequest) -> MockResponse {
+        let cursor = |name: &str| {
+            request
+                .query_param(name)
+                .and_then(|cursor| cursor.parse::<usize>().ok())
+        };
+        let (from, to) = (cursor("from"), cursor("to"));
+        let limit = cursor("limit").unwrap_or(100);
+
+        let mut edges: Vec<Value> = self
+            .schedule
+            .iter()
+            .filter(|(pid, _, _)| pid == process_id)
+            .enumerate()
+            .filter(|(nonce, _)| from.is_none_or(|from| *nonce > from))
+            .filter(|(nonce, _)| to.is_none_or(|to| *nonce <= to))
+            .map(|(nonce, scheduled)| {
+                json!({"cursor": nonce.to_string(), "node": scheduled_json(nonce, scheduled)})
+            })
+            .take(limit + 1)
+            .collect();
+        let has_next_page = edges.len() > limit;
+        edges.truncate(limit);
+        MockResponse::json(json!({
+            "page_info": {"has_next_page": has_next_page},
+            "edges": edges,
+        }))
+    }
+
+    fn receive<F>(&mut self, body: &[u8], response: F) -> MockResponse
+    where
+        F: Fn(&DataItem) -> Value,
+    {
+        match DataItem::from_bytes(body) {
+            Ok(item) => {
+                let response = MockResponse::json(response(&item));
+                self.items.push(item);
+                response
+            }
+            Err(e) => MockResponse::new(400).body(e.to_string()),
+        }
+    }
+
+    fn results_page(&self, process_id: &str, request: &MockRequest) -> MockResponse {
+        let results = self.results.iter().filter(|(id, _, _)| id == process_id);
+        let from = request.query_param("from");
+        let limit = request
+            .query_param("limit")
+            .and_then(|limit| limit.parse().ok())
+            .unwrap_or(usize::MAX);
+
+        let edges: Vec<Value> = results
+            .skip_while(|(_, message_id, _)| from.is_some() && from.as_ref() != Some(message_id))
+            .skip(from.is_some() as usize)
+            .take(limit)
+            .map(|(_, message_id, result)| json!({"cursor": message_id, "node": result}))
+            .collect();
+        MockResponse::json(json!({ "edges": edges }))
+    }
+}
+
+// a scheduled message as the SU returns it, with its assignment
+fn scheduled_json(nonce: usize, (process_id, timestamp, item): &(String, u64, DataItem)) -> Value {
+    let assignment_id =
+        BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(format!("{}/{}", item.id(), nonce)));
+    json!({
+        "message": {
+            "id": item.id(),
+            "owner": {
+                "address": item.owner_address(),
+                "key": BASE64_URL_SAFE_NO_PAD.encode(item.owner()),
+            },
+            "tags": item.tags(),
+            "data": String::from_utf8_lossy(item.data()),
+            "anchor": item.anchor().map(|anchor| String::from_utf8_lossy(anchor).to_string()),
+            "target": process_id,
+        },
+        "assignment": {
+            "id": assignment_id,
+            "owner": {"address": MOCK_NODE_ADDRESS, "key": ""},
+            "tags": [
+                {"name": "Process", "value": process_id},
+                {"name": "Epoch", "value": "0"},
+                {"name": "Nonce", "value": nonce.to_string()},
+                {"name": "Timestamp", "value": timestamp.to_string()},
+                {"name": "Block-Height", "value": "0"},
+                {"name": "Message", "value": item.id()},
+            ],
+        },
+    })
+}
+
+fn empty_result() -> Value {
+    serde_json::from_str(EMPTY_RESULT).unwrap()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::AoErrors;
+    use crate::scheme::{ResultsQuery, ScheduleQuery, Tag};
+    use crate::testing::{MockResponse, MockServer};
+    use crate::wallet::SignerTypes;
+    use axum::http::Method;
+    use futures::TryStreamExt;
+    use serde_json::json;
+
+    const PROCESS_ID: &str = "xU9zFkq3X2ZQ6olwNVvr1vUWIjc3kXTWr7xKQD6dh10";
+
+    fn signer() -> SignerTypes {
+        SignerTypes::Arweave("test_key.json".to_string())
+    }
+
+    #[tokio::test]
+    pub async fn test_mock_mu_and_cu() {
+        let mock = MockServer::start().await.unwrap();
+        let ao = mock.legacy(signer());
+
+        let res = ao
+            .send(
+                PROCESS_ID.to_string(),
+                b"ping".to_vec(),
+                vec![Tag::new("Action", "Ping")],
+            )
+            .await
+            .unwrap();
+        let items = mock.received_items();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].id(), res.id);
+        assert_eq!(items[0].tag("Action"), Some("Ping"));
+        assert!(items[0].verify().is_ok());
+
+        mock.set_result(PROCESS_ID, &res.id, json!({"Output": {"data": "pong"}, "Messages": []}));
+        let result = ao.get(PROCESS_ID.to_string(), res.id.clone()).await.unwrap();
+        assert_eq!(result.output.data, json!("pong"));
+
+        mock.set_result(PROCESS_ID, "second", json!({"Output": "", "Messages": []}));
+        let edges: Vec<_> = ao
+            .results_stream(PROCESS_ID.to_string(), ResultsQuery::default())
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(edges.len(), 2);
+
+        mock.set_dry_run(PROCESS_ID, json!({"Output": "", "Error": "boom"}));
+        let res = ao.dry_run(PROCESS_ID.to_string(), String::new(), vec![]).await;
+        assert!(matches!(res, Err(AoErrors::ProcessFailed { .. })));
+    }
+
+    #[tokio::test]
+    pub async fn test_mock_su() {
+        let mock = MockServer::start().await.unwrap();
+        let ao = mock.legacy(signer());
+        let su = mock.su();
+
+        let mut ids = vec![];
+        for i in 0..3 {
+            let data = format!("ping {}", i).into_bytes();
+            let res = ao.send(PROCESS_ID.to_string(), data, vec![]).await.unwrap();
+            ids.push(res.id);
+        }
+
+        let query = ScheduleQuery {
+            limit: Some(2),
+            ..Default::default()
+        };
+        let page = su.schedule(PROCESS_ID.to_string(), &query).await.unwrap();
+        assert!(page.page_info.has_next_page);
+        assert_eq!(page.edges.len(), 2);
+        let node = &page.edges[1].node;
+        assert_eq!(node.message.as_ref().unwrap().id, ids[1]);
+        assert_eq!(node.message.as_ref().unwrap().data.as_deref(), Some("ping 1"));
+        assert_eq!(node.assignment.nonce(), Some(1));
+
+        let edges: Vec<_> = su
+            .schedule_stream(PROCESS_ID.to_string(), query)
+            .try_collect()
+            .await
+            .unwrap();
+        let scheduled: Vec<_> = edges
+            .iter()
+            .map(|edge| edge.node.message.as_ref().unwrap().id.clone())
+            .collect();
+        assert_eq!(scheduled, ids);
+
+        let message = su.message(PROCESS_ID.to_string(), ids[2].clone()).await.unwrap();
+        assert_eq!(message.message.unwrap().id, ids[2]);
+        assert_eq!(message.assignment.nonce(), Some(2));
+        assert_eq!(message.assignment.tag("Message"), Some(ids[2].as_str()));
+
+        let res = su.message(PROCESS_ID.to_string(), "unknown".to_string()).await;
+        assert_eq!(res.unwrap_err().status(), Some(404));
+    }
+
+    #[tokio::test]
+    pub async fn test_mock_redirect_and_script() {
+        let router = MockServer::start().await.unwrap();
+        let cu = MockServer::start().await.unwrap();
+        router.redirect_process(PROCESS_ID, &cu.url());
+
+        let ao = router.legacy(signer());
+        ao.get(PROCESS_ID.to_string(), "msg".to_string()).await.unwrap();
+        assert_eq!(ao.cu_url_for(PROCESS_ID), cu.url());
+        assert_eq!(cu.requests()[0].path, "/result/msg");
+        assert_eq!(
+            cu.requests()[0].query_param("process-id").as_deref(),
+            Some(PROCESS_ID)
+        );
+
+        router.respond(Method::POST, "/", MockResponse::new(503));
+        let res = ao.send(PROCESS_ID.to_string(), vec![], vec![]).await;
+        assert!(res.unwrap_err().is_retryable());
+        assert!(router.received_items().is_empty());
+    }
+
+    #[tokio::test]
+    pub async fn test_mock_hyperbeam() {
+        let mock = MockServer::start().await.unwrap();
+        mock.set_process_state(PROCESS_ID, json!({"balance": 10}));
+
+        let state = mock
+            .hyperbeam(signer())
+            .process_now(PROCESS_ID.to_string())
+            .await
+            .unwrap();
+        assert_eq!(state, json!({"balance": 10}));
+        assert!(mock.hyperbeam(signer()).process_now("unknown".to_string()).await.is_err());
+    }
+}
@@ -0,0 +1,307 @@
+use crate::ao::Legacy;
+use crate::errors::{response_json, AoErrors, HttpError};
+use crate::scheme::{OutboxMessage, ResponseCu, DEFAULT_GATEWAY};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+pub const DEFAULT_TRACE_DEPTH: usize = 8;
+
+/// A message result along with the messages it pushed to other processes
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MessageTrace {
+    pub process_id: String,
+    pub message_id: String,
+    pub result: ResponseCu,
+    pub outbox: Vec<OutboxTrace>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OutboxTrace {
+    pub message: OutboxMessage,
+    pub next: TraceLink,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum TraceLink {
+    Traced(Box<MessageTrace>),
+    /// The pushed message could not be found on the gateway
+    Unresolved,
+    DepthLimit {
+        message_id: String,
+    },
+    /// The pushed message is one of its own ancestors
+    Cycle {
+        message_id: String,
+    },
+    /// The pushed message was already traced in another branch of the tree
+    AlreadyTraced {
+        message_id: String,
+    },
+    /// Looking up the pushed message id (`message_id` is then `None`) or
+    /// fetching its result failed
+    Failed {
+        message_id: Option<String>,
+        error: String,
+    },
+}
+
+/// Follows the outbox messages of a result across processes. Pushed message
+/// ids are resolved through the gateway GraphQL API, matching the target
+/// process with the `From-Process` and `Reference` tags set by the MU.
+pub struct Tracer<'a> {
+    ao: &'a Legacy,
+    gateway_url: String,
+    max_depth: usize,
+}
+
+impl<'a> Tracer<'a> {
+    pub fn new(ao: &'a Legacy) -> Self {
+        Self {
+            ao,
+            gateway_url: DEFAULT_GATEWAY.to_string(),
+            max_depth: DEFAULT_TRACE_DEPTH,
+        }
+    }
+
+    pub fn gateway_url(mut self, gateway_url: String) -> Self {
+        self.gateway_url = gateway_url;
+        self
+    }
+
+    /// Number of hops followed from the root message
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub async fn trace(
+        &self,
+        process_id: String,
+        message_id: String,
+    ) -> Result<MessageTrace, AoErrors> {
+        let mut visited = Visited::default();
+        self.trace_message(process_id, message_id, 0, &mut visited)
+            .await
+    }
+
+    fn trace_message<'b>(
+        &'b self,
+        process_id: String,
+        message_id: String,
+        depth: usize,
+        visited: &'b mut Visited,
+    ) -> BoxFuture<'b, Result<MessageTrace, AoErrors>> {
+        Box::pin(async move {
+            let result = self
+                .ao
+                .get_raw(process_id.clone(), message_id.clone())
+                .await?;
+            let mut outbox = Vec::with_capacity(result.messages.len());
+            visited.path.push(message_id.clone());
+            visited.traced.insert(message_id.clone());
+
+            for message in result.messages.iter().cloned() {
+                let next = match self.pushed_id(&process_id, &message).await {
+                    Ok(Some(pushed_id)) if visited.path.contains(&pushed_id) => TraceLink::Cycle {
+                        message_id: pushed_id,
+                    },
+                    Ok(Some(pushed_id)) if visited.traced.contains(&pushed_id) => {
+                        TraceLink::AlreadyTraced {
+                            message_id: pushed_id,
+                        }
+                    }
+                    Ok(Some(pushed_id)) if depth >= self.max_depth => TraceLink::DepthLimit {
+                        message_id: pushed_id,
+                    },
+                    Ok(Some(pushed_id)) => match self
+                        .trace_message(
+                            message.target.clone(),
+                            pushed_id.clone(),
+                            depth + 1,
+                            visited,
+                        )
+                        .await
+                    {
+                        Ok(trace) => TraceLink::Traced(Box::new(trace)),
+                        Err(e) => TraceLink::Failed {
+                            message_id: Some(pushed_id),
+                            error: e.to_string(),
+                        },
+                    },
+                    Ok(None) => TraceLink::Unresolved,
+                    Err(e) => TraceLink::Failed {
+                        message_id: None,
+                        error: e.to_string(),
+                    },
+                };
+                outbox.push(OutboxTrace { message, next });
+            }
+            visited.path.pop();
+
+            Ok(MessageTrace {
+                process_id,
+                message_id,
+                result,
+                outbox,
+            })
+        })
+    }
+
+    async fn pushed_id(
+        &self,
+        process_id: &str,
+        message: &OutboxMessage,
+    ) -> Result<Option<String>, AoErrors> {
+        let Some(reference) = message.tag("Reference") else {
+            return Ok(None);
+        };
+
+        let query = json!({
+            "query": "query ($recipients: [String!], $tags: [TagFilter!]) { transactions(recipients: $recipients, tags: $tags, first: 1) { edges { node { id } } } }",
+            "variables": {
+                "recipients": [message.target],
+                "tags": [
+                    { "name": "From-Process", "values": [process_id] },
+                    { "name": "Reference", "values": [reference] },
+                ],
+            },
+        });
+
+        let url = format!("{}/graphql", self.gateway_url);
+        let res = self
+            .ao
+            .client()
+            .post(&url)
+            .json(&query)
+            .send()
+            .await
+            .map_err(|e| AoErrors::InvalidServerResponse(HttpError::request(&url, e)))?;
+        let res = response_json::<Value>(res).await?;
+
+        Ok(first_transaction_id(&res))
+    }
+}
+
+// the ancestors of the message being traced, and every message traced so far
+#[derive(Default)]
+struct Visited {
+    path: Vec<String>,
+    traced: HashSet<String>,
+}
+
+fn first_transaction_id(res: &Value) -> Option<String> {
+    res.pointer("/data/transactions/edges/0/node/id")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+}
+
+impl Legacy {
+    pub fn tracer(&self) -> Tracer<'_> {
+        Tracer::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{MockResponse, MockServer};
+    use crate::trace::{first_transaction_id, TraceLink};
+    use crate::wallet::SignerTypes;
+    use reqwest::Method;
+    use serde_json::{json, Value};
+
+    fn pushed(id: &str) -> MockResponse {
+        MockResponse::json(json!({
+            "data": { "transactions": { "edges": [{ "node": { "id": id } }] } }
+        }))
+    }
+
+    fn send(target: &str, reference: &str) -> Value {
+        json!({"Target": target, "Tags": [{"name": "Reference", "value": reference}]})
+    }
+
+    #[tokio::test]
+    pub async fn test_trace_cycle_and_unresolved() {
+        let mock = MockServer::start().await.unwrap();
+        // m0 on a pushes to b (m1), b pushes back to a (resolved to m0 again),
+        // to c (not found), to d (lookup fails) and a message without Reference
+        mock.set_result("a", "m0", json!({"Messages": [send("b", "1")]}));
+        mock.set_result(
+            "b",
+            "m1",
+            json!({"Messages": [send("a", "2"), send("c", "3"), send("d", "4"), {"Target": "e"}]}),
+        );
+        mock.respond(Method::POST, "/graphql", pushed("m1"));
+        mock.respond(Method::POST, "/graphql", pushed("m0"));
+        mock.respond(
+            Method::POST,
+            "/graphql",
+            MockResponse::json(json!({"data": {"transactions": {"edges": []}}})),
+        );
+        mock.respond(Method::POST, "/graphql", MockResponse::new(500));
+
+        let ao = mock.legacy(SignerTypes::Arweave("test_key.json".to_string()));
+        let trace = ao
+            .tracer()
+            .gateway_url(mock.url())
+            .trace("a".to_string(), "m0".to_string())
+            .await
+            .unwrap();
+
+        let TraceLink::Traced(m1) = &trace.outbox[0].next else {
+            panic!("unexpected link: {:?}", trace.outbox[0].next);
+        };
+        assert_eq!(m1.process_id, "b");
+        let links: Vec<&TraceLink> = m1.outbox.iter().map(|o| &o.next).collect();
+        assert!(matches!(links[0], TraceLink::Cycle { message_id } if message_id == "m0"));
+        assert!(matches!(links[1], TraceLink::Unresolved));
+        assert!(matches!(links[2], TraceLink::Failed { message_id: None, .. }));
+        assert!(matches!(links[3], TraceLink::Unresolved));
+    }
+
+    #[tokio::test]
+    pub async fn test_trace_depth_limit() {
+        let mock = MockServer::start().await.unwrap();
+        mock.set_result("a", "m0", json!({"Messages": [send("b", "1"), send("e", "4")]}));
+        mock.set_result("b", "m1", json!({"Messages": [send("c", "2"), send("d", "3")]}));
+        for id in ["m1", "m2", "m1", "m1"] {
+            mock.respond(Method::POST, "/graphql", pushed(id));
+        }
+
+        let ao = mock.legacy(SignerTypes::Arweave("test_key.json".to_string()));
+        let trace = ao
+            .tracer()
+            .gateway_url(mock.url())
+            .max_depth(1)
+            .trace("a".to_string(), "m0".to_string())
+            .await
+            .unwrap();
+
+        let TraceLink::Traced(m1) = &trace.outbox[0].next else {
+            panic!("unexpected link: {:?}", trace.outbox[0].next);
+        };
+        assert!(matches!(&m1.outbox[0].next, TraceLink::DepthLimit { message_id } if message_id == "m2"));
+        // m1 is an ancestor here, not a message of another branch
+        assert!(matches!(&m1.outbox[1].next, TraceLink::Cycle { message_id } if message_id == "m1"));
+        // while from the second branch of m0 it is a duplicate
+        assert!(matches!(
+            &trace.outbox[1].next,
+            TraceLink::AlreadyTraced { message_id } if message_id == "m1"
+        ));
+    }
+
+    #[test]
+    pub fn test_first_transaction_id() {
+        let res = json!({
+            "data": { "transactions": { "edges": [{ "node": { "id": "5JtjkYy1hk0Zce5mP6gDWIOdt9rCSQAFX-K9jZnqniw" } }] } }
+        });
+        assert_eq!(
+            first_transaction_id(&res).as_deref(),
+            Some("5JtjkYy1hk0Zce5mP6gDWIOdt9rCSQAFX-K9jZnqniw")
+        );
+
+        let empty = json!({ "data": { "transactions": { "edges": [] } } });
+        assert!(first_transaction_id(&empty).is_none());
+    }
+}
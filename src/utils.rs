@@ -1,8 +1,12 @@
 use base64::{engine::general_purpose, Engine as _};
+use rand::RngCore;
+use reqwest::header::HeaderMap;
+use reqwest::redirect::Policy;
+use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use tiny_keccak::{Hasher, Keccak};
 use std::fs;
-use serde_json::from_str;
+use std::time::Duration;
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Node {
@@ -12,10 +16,58 @@ pub struct Node {
     pub address: String
 }
 
+/// Settings of the HTTP client shared by a `Legacy` or `Hyperbeam` instance
+#[derive(Debug, Clone)]
+pub struct HttpConfig {
+    pub timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    pub user_agent: String,
+    pub default_headers: HeaderMap,
+    pub follow_redirects: bool,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            timeout: None,
+            connect_timeout: None,
+            user_agent: concat!("rusty-ao/", env!("CARGO_PKG_VERSION")).to_string(),
+            default_headers: HeaderMap::new(),
+            follow_redirects: true,
+        }
+    }
+}
+
+impl HttpConfig {
+    pub fn build_client(&self) -> Result<Client, reqwest::Error> {
+        let mut builder = Client::builder()
+            .user_agent(self.user_agent.clone())
+            .default_headers(self.default_headers.clone());
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        if !self.follow_redirects {
+            builder = builder.redirect(Policy::none());
+        }
+        builder.build()
+    }
+}
+
 pub fn encode_base64(input: &str) -> String {
     general_purpose::STANDARD.encode(input.as_bytes())
 }
 
+/// Generates a random data item anchor: 24 random bytes, base64url-encoded
+/// into the 32 bytes required by ANS-104.
+pub fn random_anchor() -> String {
+    let mut bytes = [0u8; 24];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
 pub fn keccak256(input: &str) -> String {
     let mut hasher = Keccak::v256();
     let mut output = [0u8; 32];
@@ -27,6 +79,6 @@ pub fn keccak256(input: &str) -> String {
 pub fn get_node(address: &str) -> Node {
     let nodes = fs::read_to_string("./compiled_node_list.json").unwrap();
     let nodes: Vec<Node> = serde_json::from_str(&nodes).unwrap();
-    let node = nodes.iter().find(|node| node.address == address).cloned().unwrap_or_else(|| Node::default());
+    let node = nodes.iter().find(|node| node.address == address).cloned().unwrap_or_default();
     node
 }
\ No newline at end of file
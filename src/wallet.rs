@@ -1,13 +1,346 @@
-use bundlr_sdk::currency::arweave::Arweave;
-use bundlr_sdk::currency::solana::Solana;
+use crate::errors::{AoErrors, BoxError};
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use bundlr_sdk::currency::arweave::{Arweave, ArweaveBuilder};
+use bundlr_sdk::currency::solana::{Solana, SolanaBuilder};
+use bundlr_sdk::currency::Currency;
+use bundlr_sdk::error::BundlrError;
+use bundlr_sdk::index::SignerMap;
+use bundlr_sdk::{Secp256k1Signer, Signer};
+use bytes::Bytes;
+use rsa::{BigUint, PaddingScheme, PublicKeyParts, RsaPrivateKey};
+use secp256k1::SecretKey;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tiny_keccak::{Hasher, Keccak};
 
 #[derive(Clone)]
 pub enum SignerTypes {
     Solana(String),
+    /// Path of an Arweave JWK file
     Arweave(String),
+    /// JSON of an Arweave JWK, e.g. a secret injected by the runtime
+    ArweaveJwk(String),
+    Ethereum(EthereumKey),
+}
+
+impl SignerTypes {
+    pub fn from_jwk(jwk: &Value) -> Self {
+        SignerTypes::ArweaveJwk(jwk.to_string())
+    }
+
+    pub fn from_jwk_bytes(jwk: &[u8]) -> Result<Self, AoErrors> {
+        let jwk = std::str::from_utf8(jwk).map_err(|e| AoErrors::ErrorConstructingSigner {
+            source: Some(e.into()),
+        })?;
+        Ok(SignerTypes::ArweaveJwk(jwk.to_string()))
+    }
+
+    /// Reads a wallet from the environment variable `name`, holding either
+    /// an Arweave JWK, a hex Ethereum private key or the path of a JWK file
+    pub fn from_env(name: &str) -> Result<Self, AoErrors> {
+        let wallet = std::env::var(name).map_err(|e| AoErrors::ErrorConstructingSigner {
+            source: Some(format!("{}: {}", name, e).into()),
+        })?;
+        let wallet = wallet.trim();
+        let hex_key = wallet.trim_start_matches("0x");
+
+        if wallet.starts_with('{') {
+            Ok(SignerTypes::ArweaveJwk(wallet.to_string()))
+        } else if hex_key.len() == 64 && hex_key.chars().all(|c| c.is_ascii_hexdigit()) {
+            Ok(SignerTypes::Ethereum(EthereumKey::Hex(wallet.to_string())))
+        } else if Path::new(wallet).is_file() {
+            Ok(SignerTypes::Arweave(wallet.to_string()))
+        } else {
+            Err(AoErrors::ErrorConstructingSigner {
+                source: Some(
+                    format!(
+                        "{} is neither a JWK, a hex private key nor a JWK file path",
+                        name
+                    )
+                    .into(),
+                ),
+            })
+        }
+    }
+
+    pub(crate) fn signer(&self) -> Result<Signers, BoxError> {
+        Ok(match self {
+            SignerTypes::Solana(key) => Signers::Solana(SolanaBuilder::new().wallet(key).build()?),
+            SignerTypes::Arweave(keypair_path) => Signers::Arweave(
+                ArweaveBuilder::new()
+                    .keypair_path(PathBuf::from(keypair_path))
+                    .build()?,
+            ),
+            SignerTypes::ArweaveJwk(jwk) => Signers::ArweaveJwk(ArweaveKey::from_jwk(jwk)?),
+            SignerTypes::Ethereum(key) => Signers::Ethereum(key.signer()?),
+        })
+    }
+}
+
+/// An Arweave RSA key parsed from an in-memory JWK. It signs like
+/// bundlr's `ArweaveSigner` (RSA-PSS over SHA-256), which only loads keys
+/// from a file.
+pub struct ArweaveKey {
+    key: RsaPrivateKey,
+    owner: Bytes,
+}
+
+#[derive(Deserialize)]
+struct RsaJwk {
+    kty: String,
+    n: String,
+    e: String,
+    d: String,
+    p: String,
+    q: String,
+}
+
+impl ArweaveKey {
+    const KEY_LENGTH: usize = 512;
+
+    pub fn from_jwk(jwk: &str) -> Result<Self, BoxError> {
+        let jwk: RsaJwk = serde_json::from_str(jwk)?;
+        if jwk.kty != "RSA" {
+            return Err(format!("unsupported JWK key type {}", jwk.kty).into());
+        }
+        let uint = |value: &str| -> Result<BigUint, BoxError> {
+            Ok(BigUint::from_bytes_be(&BASE64_URL_SAFE_NO_PAD.decode(value)?))
+        };
+        let key = RsaPrivateKey::from_components(
+            uint(&jwk.n)?,
+            uint(&jwk.e)?,
+            uint(&jwk.d)?,
+            vec![uint(&jwk.p)?, uint(&jwk.q)?],
+        );
+        key.validate()?;
+
+        let owner = key.n().to_bytes_be();
+        if owner.len() != Self::KEY_LENGTH {
+            return Err(format!("Arweave keys must be {} bytes", Self::KEY_LENGTH).into());
+        }
+        Ok(Self {
+            key,
+            owner: owner.into(),
+        })
+    }
+}
+
+impl Signer for ArweaveKey {
+    fn sign(&self, message: Bytes) -> Result<Bytes, BundlrError> {
+        let padding = PaddingScheme::new_pss::<Sha256, _>(rand::thread_rng());
+        let signature = self
+            .key
+            .sign(padding, &Sha256::digest(&message))
+            .map_err(|e| BundlrError::SigningError(e.to_string()))?;
+        Ok(signature.into())
+    }
+
+    fn sig_type(&self) -> SignerMap {
+        SignerMap::Arweave
+    }
+
+    fn get_sig_length(&self) -> u16 {
+        Self::KEY_LENGTH as u16
+    }
+
+    fn get_pub_length(&self) -> u16 {
+        Self::KEY_LENGTH as u16
+    }
+
+    fn pub_key(&self) -> Bytes {
+        self.owner.clone()
+    }
+}
+
+/// An EVM private key, signing data items with ANS-104 signature type 3
+#[derive(Clone)]
+pub enum EthereumKey {
+    /// Hex encoded private key, with or without the `0x` prefix
+    Hex(String),
+    /// Path and password of a V3 JSON keystore
+    Keystore { path: String, password: String },
+}
+
+impl EthereumKey {
+    pub(crate) fn signer(&self) -> Result<Secp256k1Signer, BoxError> {
+        let key = match self {
+            EthereumKey::Hex(key) => hex::decode(key.trim().trim_start_matches("0x"))?,
+            EthereumKey::Keystore { path, password } => eth_keystore::decrypt_key(path, password)?,
+        };
+        Ok(Secp256k1Signer::new(SecretKey::from_slice(&key)?))
+    }
 }
 
 pub enum Signers {
     Solana(Solana),
     Arweave(Arweave),
+    ArweaveJwk(ArweaveKey),
+    Ethereum(Secp256k1Signer),
+}
+
+impl Signers {
+    pub fn raw_signer(&self) -> Result<&dyn Signer, AoErrors> {
+        match self {
+            Signers::Solana(solana) => solana.get_signer().map_err(|e| AoErrors::InvalidSigner {
+                source: Some(e.into()),
+            }),
+            Signers::Arweave(ar) => ar.get_signer().map_err(|e| AoErrors::InvalidSigner {
+                source: Some(e.into()),
+            }),
+            Signers::ArweaveJwk(key) => Ok(key),
+            Signers::Ethereum(signer) => Ok(signer),
+        }
+    }
+}
+
+/// The EIP-55 checksummed address of an uncompressed secp256k1 public key,
+/// as used by aoconnect for the owner of Ethereum signed messages
+pub fn ethereum_address(pub_key: &[u8]) -> String {
+    let key = pub_key.strip_prefix(&[0x04]).unwrap_or(pub_key);
+    let address = hex::encode(&keccak(key)[12..]);
+    let hash = keccak(address.as_bytes());
+
+    let checksummed: String = address
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = (hash[i / 2] >> if i % 2 == 0 { 4 } else { 0 }) & 0x0f;
+            if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect();
+    format!("0x{}", checksummed)
+}
+
+fn keccak(input: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    let mut output = [0u8; 32];
+    hasher.update(input);
+    hasher.finalize(&mut output);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::signer::{AoSigner, WalletSigner};
+    use crate::wallet::{ethereum_address, EthereumKey, SignerTypes};
+    use bundlr_sdk::{ArweaveSigner, Signer, Verifier};
+
+    const KEY: &str = "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+
+    #[test]
+    pub fn test_ethereum_address() {
+        let signer = EthereumKey::Hex(KEY.to_string()).signer().unwrap();
+        assert_eq!(signer.pub_key().len(), 65);
+        assert_eq!(
+            ethereum_address(&signer.pub_key()),
+            "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23"
+        );
+        assert!(EthereumKey::Hex("0x1234".to_string()).signer().is_err());
+    }
+
+    #[test]
+    pub fn test_ethereum_keystore() {
+        let dir = std::env::temp_dir();
+        let key = hex::decode(KEY.trim_start_matches("0x")).unwrap();
+        eth_keystore::encrypt_key(
+            &dir,
+            &mut rand::thread_rng(),
+            &key,
+            "password",
+            Some("rusty-ao-test-keystore"),
+        )
+        .unwrap();
+        let path = dir.join("rusty-ao-test-keystore").to_string_lossy().to_string();
+
+        let signer = EthereumKey::Keystore {
+            path: path.clone(),
+            password: "password".to_string(),
+        }
+        .signer()
+        .unwrap();
+        assert_eq!(
+            ethereum_address(&signer.pub_key()),
+            "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23"
+        );
+
+        let wrong = EthereumKey::Keystore {
+            path: path.clone(),
+            password: "wrong".to_string(),
+        };
+        assert!(wrong.signer().is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    fn arweave_owner(signer: &SignerTypes) -> Vec<u8> {
+        WalletSigner::load(signer).unwrap().owner()
+    }
+
+    #[test]
+    pub fn test_signer_from_jwk() {
+        let jwk = std::fs::read_to_string("test_key.json").unwrap();
+        let owner = arweave_owner(&SignerTypes::Arweave("test_key.json".to_string()));
+
+        let value: serde_json::Value = serde_json::from_str(&jwk).unwrap();
+        assert_eq!(arweave_owner(&SignerTypes::from_jwk(&value)), owner);
+        assert_eq!(
+            arweave_owner(&SignerTypes::from_jwk_bytes(jwk.as_bytes()).unwrap()),
+            owner
+        );
+        assert!(SignerTypes::ArweaveJwk("not a jwk".to_string()).signer().is_err());
+
+        // the public exponent does not match the private one
+        let mut broken = value.clone();
+        broken["e"] = serde_json::json!("Aw");
+        assert!(SignerTypes::from_jwk(&broken).signer().is_err());
+    }
+
+    #[tokio::test]
+    pub async fn test_jwk_signature() {
+        let jwk = std::fs::read_to_string("test_key.json").unwrap();
+        let signer = WalletSigner::load(&SignerTypes::ArweaveJwk(jwk)).unwrap();
+        let signature = AoSigner::sign(&signer, b"hello".to_vec()).await.unwrap();
+        assert_eq!(signature.len(), signer.signature_length());
+        ArweaveSigner::verify(
+            signer.owner().into(),
+            b"hello".to_vec().into(),
+            signature.into(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    pub fn test_signer_from_env() {
+        let jwk = std::fs::read_to_string("test_key.json").unwrap();
+        std::env::set_var("RUSTY_AO_TEST_JWK", &jwk);
+        assert!(matches!(
+            SignerTypes::from_env("RUSTY_AO_TEST_JWK").unwrap(),
+            SignerTypes::ArweaveJwk(_)
+        ));
+
+        std::env::set_var("RUSTY_AO_TEST_ETH", KEY);
+        assert!(matches!(
+            SignerTypes::from_env("RUSTY_AO_TEST_ETH").unwrap(),
+            SignerTypes::Ethereum(EthereumKey::Hex(_))
+        ));
+
+        std::env::set_var("RUSTY_AO_TEST_PATH", "test_key.json");
+        assert!(matches!(
+            SignerTypes::from_env("RUSTY_AO_TEST_PATH").unwrap(),
+            SignerTypes::Arweave(_)
+        ));
+
+        let unset = SignerTypes::from_env("RUSTY_AO_TEST_UNSET").err().unwrap();
+        assert!(unset.to_string().contains("RUSTY_AO_TEST_UNSET"));
+
+        std::env::set_var("RUSTY_AO_TEST_BAD", "not a wallet");
+        let bad = SignerTypes::from_env("RUSTY_AO_TEST_BAD").err().unwrap();
+        assert!(std::error::Error::source(&bad).is_some());
+        assert!(bad.to_string().contains("neither a JWK"));
+    }
 }